@@ -0,0 +1,20 @@
+use crate::cli::OutputFormat;
+use crate::config::AppConfig;
+use crate::domain::types::export::ExportFile;
+use crate::infrastructure::persistence::json::Json;
+use crate::infrastructure::repositories::data_repository::DataRepository;
+use crate::ingest::data_getter::DataGetter;
+
+/// Сохраняет разобранный экспорт в JSON того же формата, что и `result.json`.
+pub async fn run(config: &AppConfig, format: OutputFormat) -> anyhow::Result<()> {
+    let mut export = DataGetter::load_export(config.input_path()?).await?;
+    if let ExportFile::Account(export) = &mut export {
+        export.retain_chats(&config.chat_selection());
+    }
+
+    Json::new(config.output_path()?.to_string(), format.is_pretty())
+        .save(&export)
+        .await?;
+
+    Ok(())
+}
@@ -0,0 +1,30 @@
+use crate::config::{AppConfig, DatabaseConfig};
+use crate::domain::types::export::ExportFile;
+use crate::infrastructure::persistence::postgres::Postgres;
+use crate::infrastructure::repositories::storage_repository::StorageRepository;
+use crate::ingest::data_getter::DataGetter;
+use anyhow::Context;
+
+pub async fn run(config: &AppConfig, database_config: &DatabaseConfig) -> anyhow::Result<()> {
+    let chats = match DataGetter::load_export(config.input_path()?).await? {
+        ExportFile::Chat(chat) => vec![chat],
+        ExportFile::Account(export) => export.into_chats(&config.chat_selection()),
+    };
+
+    let pool = sea_orm::Database::connect(database_config.url()?)
+        .await
+        .context("Failed to connect to database")?;
+    let storage = Postgres { pool };
+
+    for chat in chats {
+        let id = chat.id;
+        let messages = chat.messages.len();
+        storage
+            .save(chat)
+            .await
+            .with_context(|| format!("Failed to import chat {id}"))?;
+        eprintln!("Imported chat {id}: {messages} messages");
+    }
+
+    Ok(())
+}
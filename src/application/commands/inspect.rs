@@ -0,0 +1,92 @@
+use crate::config::AppConfig;
+use crate::domain::types::chat::Message;
+use crate::domain::types::export::PersonalInformation;
+use crate::ingest::data_getter::DataGetter;
+use crate::ingest::message_stream::{ChatHeader, ChatSink};
+use chrono::{DateTime, Utc};
+
+/// Краткая сводка по чату экспорта.
+struct ChatSummary {
+    header: ChatHeader,
+    messages: usize,
+    first: Option<DateTime<Utc>>,
+    last: Option<DateTime<Utc>>,
+}
+
+#[derive(Default)]
+struct InspectSink {
+    owner: Option<PersonalInformation>,
+    chats: Vec<ChatSummary>,
+}
+
+impl ChatSink for InspectSink {
+    fn personal_information(&mut self, info: PersonalInformation) -> anyhow::Result<()> {
+        self.owner = Some(info);
+        Ok(())
+    }
+
+    fn start_chat(&mut self, header: ChatHeader) -> anyhow::Result<()> {
+        self.chats.push(ChatSummary {
+            header,
+            messages: 0,
+            first: None,
+            last: None,
+        });
+        Ok(())
+    }
+
+    fn message(&mut self, message: Message) -> anyhow::Result<()> {
+        if let Some(chat) = self.chats.last_mut() {
+            chat.messages += 1;
+            chat.first = Some(
+                chat.first
+                    .map_or(message.date, |first| first.min(message.date)),
+            );
+            chat.last = chat.last.max(Some(message.date));
+        }
+        Ok(())
+    }
+
+    fn end_chat(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+}
+
+/// Выводит список чатов экспорта, не загружая его целиком в память.
+pub async fn run(config: &AppConfig) -> anyhow::Result<()> {
+    let mut sink = InspectSink::default();
+    DataGetter::stream_file(config.input_path()?, &mut sink).await?;
+
+    if let Some(owner) = &sink.owner {
+        let name = [owner.first_name.as_deref(), owner.last_name.as_deref()]
+            .into_iter()
+            .flatten()
+            .filter(|part| !part.is_empty())
+            .collect::<Vec<_>>()
+            .join(" ");
+        println!("owner: user{} {name}", owner.user_id);
+    }
+
+    println!(
+        "{:<16} {:<20} {:>9}  {:<10}  {:<10}  NAME",
+        "ID", "TYPE", "MESSAGES", "FIRST", "LAST"
+    );
+    for chat in &sink.chats {
+        let date = |date: Option<DateTime<Utc>>| {
+            date.map(|date| date.format("%Y-%m-%d").to_string())
+                .unwrap_or_else(|| "-".to_string())
+        };
+
+        println!(
+            "{:<16} {:<20} {:>9}  {:<10}  {:<10}  {}",
+            chat.header.id,
+            chat.header.r#type,
+            chat.messages,
+            date(chat.first),
+            date(chat.last),
+            chat.header.name.as_deref().unwrap_or("-"),
+        );
+    }
+
+    Ok(())
+}
@@ -0,0 +1,5 @@
+pub mod export;
+pub mod import;
+pub mod inspect;
+pub mod serve;
+pub mod stats;
@@ -0,0 +1,154 @@
+use crate::cli::ServeArgs;
+use crate::config::AppConfig;
+use anyhow::Context;
+use std::path::{Component, Path, PathBuf};
+use tokio::io::{AsyncBufReadExt, AsyncWriteExt, BufReader};
+use tokio::net::{TcpListener, TcpStream};
+
+/// Минимальный HTTP-сервер для фронтенда: `/stats.json` отдаёт файл
+/// статистики, `/media/<путь>` — медиафайлы экспорта.
+pub async fn run(config: &AppConfig, args: ServeArgs) -> anyhow::Result<()> {
+    let stats_path = match args.stats {
+        Some(stats_path) => stats_path,
+        None => config.output_path()?.to_string(),
+    };
+    let media_dir = match args.media_dir {
+        Some(media_dir) => PathBuf::from(media_dir),
+        None => source_dir(&stats_path).await?,
+    };
+
+    let listener = TcpListener::bind(&args.listen)
+        .await
+        .with_context(|| format!("Failed to listen on {}", args.listen))?;
+    eprintln!(
+        "Serving {stats_path} and {} on http://{}",
+        media_dir.display(),
+        listener.local_addr()?
+    );
+
+    loop {
+        let (stream, _) = listener.accept().await?;
+        let stats_path = stats_path.clone();
+        let media_dir = media_dir.clone();
+
+        tokio::spawn(async move {
+            if let Err(err) = handle(stream, &stats_path, &media_dir).await {
+                eprintln!("request failed: {err:#}");
+            }
+        });
+    }
+}
+
+/// Каталог медиафайлов из поля `source_dir` статистики чата (или первого
+/// чата экспорта аккаунта).
+async fn source_dir(stats_path: &str) -> anyhow::Result<PathBuf> {
+    let content = tokio::fs::read(stats_path)
+        .await
+        .with_context(|| format!("Failed to read {stats_path}"))?;
+    let stats: serde_json::Value = serde_json::from_slice(&content)
+        .with_context(|| format!("Failed to parse {stats_path}"))?;
+
+    stats
+        .get("source_dir")
+        .or_else(|| stats.get("chats")?.get(0)?.get("source_dir"))
+        .and_then(|source_dir| source_dir.as_str())
+        .map(PathBuf::from)
+        .context("No source_dir in stats, pass --media-dir")
+}
+
+async fn handle(mut stream: TcpStream, stats_path: &str, media_dir: &Path) -> anyhow::Result<()> {
+    let mut reader = BufReader::new(&mut stream);
+    let mut request_line = String::new();
+    reader.read_line(&mut request_line).await?;
+    loop {
+        let mut header = String::new();
+        if reader.read_line(&mut header).await? == 0 || header.trim().is_empty() {
+            break;
+        }
+    }
+
+    let mut parts = request_line.split_whitespace();
+    let method = parts.next().unwrap_or_default();
+    let target = parts.next().unwrap_or("/");
+    let path = target.split(['?', '#']).next().unwrap_or_default();
+
+    let file = match (method, path) {
+        ("GET", "/" | "/stats.json") => Some(PathBuf::from(stats_path)),
+        ("GET", path) => path
+            .strip_prefix("/media/")
+            .and_then(percent_decode)
+            .and_then(|relative| media_path(media_dir, &relative)),
+        _ => None,
+    };
+
+    let response = match file {
+        Some(file) => tokio::fs::read(&file)
+            .await
+            .ok()
+            .map(|body| (content_type(&file), body)),
+        None => None,
+    };
+
+    let (status, content_type, body) = match response {
+        Some((content_type, body)) => ("200 OK", content_type, body),
+        None => ("404 Not Found", "text/plain", b"Not Found".to_vec()),
+    };
+    let head = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: {content_type}\r\nContent-Length: {}\r\n\
+         Access-Control-Allow-Origin: *\r\nConnection: close\r\n\r\n",
+        body.len()
+    );
+
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(&body).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+/// Путь внутри `media_dir`; `..` и абсолютные пути отклоняются.
+fn media_path(media_dir: &Path, relative: &str) -> Option<PathBuf> {
+    let relative = Path::new(relative);
+    relative
+        .components()
+        .all(|component| matches!(component, Component::Normal(_)))
+        .then(|| media_dir.join(relative))
+}
+
+fn percent_decode(value: &str) -> Option<String> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = value.get(i + 1..i + 3)?;
+            decoded.push(u8::from_str_radix(hex, 16).ok()?);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    String::from_utf8(decoded).ok()
+}
+
+fn content_type(path: &Path) -> &'static str {
+    match path
+        .extension()
+        .and_then(|extension| extension.to_str())
+        .map(|extension| extension.to_ascii_lowercase())
+        .as_deref()
+    {
+        Some("json") => "application/json",
+        Some("jpg" | "jpeg") => "image/jpeg",
+        Some("png") => "image/png",
+        Some("gif") => "image/gif",
+        Some("webp") => "image/webp",
+        Some("webm") => "video/webm",
+        Some("mp4") => "video/mp4",
+        Some("tgs") => "application/x-tgsticker",
+        Some("ogg" | "oga") => "audio/ogg",
+        Some("mp3") => "audio/mpeg",
+        _ => "application/octet-stream",
+    }
+}
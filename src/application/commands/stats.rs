@@ -0,0 +1,97 @@
+use crate::application::services::data_processor::DataProcessor;
+use crate::application::services::stats_collector::StatsCollector;
+use crate::cli::OutputFormat;
+use crate::config::AppConfig;
+use crate::domain::types::export::ExportFile;
+use crate::domain::types::stats::{AllStats, ExportStats, StatsReport};
+use crate::infrastructure::persistence::json::Json;
+use crate::ingest::data_getter::DataGetter;
+use crate::ingest::export_archive::ExportArchive;
+use anyhow::Context;
+use std::path::Path;
+
+pub async fn run(config: &AppConfig, format: OutputFormat) -> anyhow::Result<()> {
+    let input_path = config.input_path()?;
+    let options = config.stats_options()?;
+    let media_dir = config.media_dir()?;
+
+    // В HTML нет id пользователей и чата: угадать владельца не по чему
+    if DataGetter::is_html_export(input_path) && config.owner_id.is_none() {
+        anyhow::bail!(
+            "HTML exports have no user ids, pass the owner's name with --owner or APP_OWNER_ID"
+        );
+    }
+
+    let data_processor = DataProcessor::new(Json::new(
+        config.output_path()?.to_string(),
+        format.is_pretty(),
+    ));
+
+    let source_dir = if ExportArchive::is_archive(input_path) {
+        media_dir.as_str()
+    } else {
+        let path = Path::new(input_path);
+        if path.is_dir() {
+            Some(path)
+        } else {
+            path.parent()
+        }
+        .and_then(|path| path.to_str())
+        .filter(|path| !path.is_empty())
+        .unwrap_or(".")
+    };
+
+    let report = if config.streaming {
+        let collector = StatsCollector::new(
+            options,
+            source_dir,
+            config.owner_id.clone(),
+            config.chat_selection(),
+        );
+
+        data_processor
+            .stream_stats_and_save(input_path, collector)
+            .await?
+    } else {
+        match DataGetter::load_export(input_path).await? {
+            ExportFile::Chat(mut chat) => {
+                let owner_id = config
+                    .owner_id
+                    .clone()
+                    .or_else(|| chat.detect_owner())
+                    .context("Failed to detect chat owner, pass --owner or APP_OWNER_ID")?;
+
+                chat.retain_by_date(&options.range).await;
+
+                let stats = data_processor
+                    .gen_stats_and_save::<AllStats>((&chat, options, source_dir, &owner_id))
+                    .await?;
+                StatsReport::Chat(Box::new(stats))
+            }
+            ExportFile::Account(export) => {
+                let owner_id = config
+                    .owner_id
+                    .clone()
+                    .or_else(|| export.owner_id())
+                    .context("Failed to detect account owner, pass --owner or APP_OWNER_ID")?;
+
+                let mut chats = export.into_chats(&config.chat_selection());
+                for chat in chats.iter_mut() {
+                    chat.retain_by_date(&options.range).await;
+                }
+
+                let stats = data_processor
+                    .gen_stats_and_save::<ExportStats>((&chats, options, source_dir, &owner_id))
+                    .await?;
+                StatsReport::Account(stats)
+            }
+        }
+    };
+
+    let extracted = DataGetter::extract_media(input_path, &report, &media_dir).await?;
+    if extracted > 0 {
+        eprintln!("Extracted {extracted} media files to {media_dir}");
+    }
+
+    Ok(())
+}
@@ -1,4 +1,7 @@
+use crate::application::services::stats_collector::{MessageResolver, StatsCollector};
+use crate::domain::types::stats::StatsReport;
 use crate::infrastructure::repositories::data_repository::DataRepository;
+use crate::ingest::data_getter::DataGetter;
 use crate::infrastructure::repositories::statistic_repository::StatisticRepository;
 use serde::Serialize;
 
@@ -17,12 +20,29 @@ where
         Self { data_repository }
     }
 
-    pub async fn gen_stats_and_save<'a, T>(&self, data: T::Data<'a>) -> anyhow::Result<()>
+    pub async fn gen_stats_and_save<'a, T>(&self, data: T::Data<'a>) -> anyhow::Result<T>
     where
         T: StatisticRepository + Serialize,
     {
         let total_stats = T::get_stats(data).await?;
         self.data_repository.save(&total_stats).await?;
-        Ok(())
+        Ok(total_stats)
+    }
+
+    pub async fn stream_stats_and_save(
+        &self,
+        file_path: &str,
+        mut collector: StatsCollector,
+    ) -> anyhow::Result<StatsReport> {
+        DataGetter::stream_file(file_path, &mut collector).await?;
+        let mut report = collector.finish()?;
+
+        let mut resolver = MessageResolver::new(&mut report);
+        if resolver.is_needed() {
+            DataGetter::stream_file(file_path, &mut resolver).await?;
+        }
+
+        self.data_repository.save(&report).await?;
+        Ok(report)
     }
 }
@@ -1 +1,2 @@
 pub mod data_processor;
+pub mod stats_collector;
@@ -0,0 +1,265 @@
+use crate::domain::types::chat::{ChatOwner, Message};
+use crate::domain::types::export::{ChatSelection, PersonalInformation};
+use crate::domain::types::options::StatsOptions;
+use crate::domain::types::stats::{AllStats, ExportStats, StatsReport};
+use crate::infrastructure::persistence::aggregators::AllStatsAggregator;
+use crate::infrastructure::repositories::aggregator::Aggregator;
+use crate::ingest::message_stream::{ChatHeader, ChatSink};
+
+/// Считает статистику прямо во время потокового разбора экспорта.
+pub struct StatsCollector {
+    options: StatsOptions,
+    source_dir: String,
+    owner_id: Option<String>,
+    selection: ChatSelection,
+    account: bool,
+    current: Option<AllStatsAggregator>,
+    chats: Vec<AllStats>,
+}
+
+impl StatsCollector {
+    pub fn new(
+        options: StatsOptions,
+        source_dir: &str,
+        owner_id: Option<String>,
+        selection: ChatSelection,
+    ) -> Self {
+        Self {
+            options,
+            source_dir: source_dir.to_string(),
+            owner_id,
+            selection,
+            account: false,
+            current: None,
+            chats: vec![],
+        }
+    }
+
+    pub fn finish(mut self) -> anyhow::Result<StatsReport> {
+        if self.account {
+            return Ok(StatsReport::Account(ExportStats {
+                owner_id: self.owner_id.unwrap_or_default(),
+                chats: self.chats,
+            }));
+        }
+
+        self.chats
+            .pop()
+            .map(|stats| StatsReport::Chat(Box::new(stats)))
+            .ok_or_else(|| anyhow::anyhow!("Export does not contain messages"))
+    }
+}
+
+impl ChatSink for StatsCollector {
+    fn start_account(&mut self) -> anyhow::Result<()> {
+        self.account = true;
+        Ok(())
+    }
+
+    fn personal_information(&mut self, info: PersonalInformation) -> anyhow::Result<()> {
+        if self.owner_id.is_none() {
+            self.owner_id = Some(format!("user{}", info.user_id));
+        }
+        Ok(())
+    }
+
+    fn start_chat(&mut self, header: ChatHeader) -> anyhow::Result<()> {
+        const PERSONAL_CHAT: &str = "personal_chat";
+
+        if self.account
+            && !self
+                .selection
+                .matches(header.id, header.name.as_deref(), &header.r#type)
+        {
+            self.current = None;
+            return Ok(());
+        }
+
+        let owner = match &self.owner_id {
+            Some(id) => ChatOwner::Id(id.clone()),
+            None if header.r#type == PERSONAL_CHAT => {
+                ChatOwner::NotPeer(format!("user{}", header.id))
+            }
+            None => anyhow::bail!(
+                "Failed to detect owner of chat {}, set APP_OWNER_ID",
+                header.id
+            ),
+        };
+
+        self.current = Some(AllStatsAggregator::new(
+            header,
+            self.options,
+            &self.source_dir,
+            owner,
+        ));
+        Ok(())
+    }
+
+    fn message(&mut self, message: Message) -> anyhow::Result<()> {
+        if let Some(current) = &mut self.current {
+            if self.options.range.contains(message.date) {
+                current.observe(&message);
+            }
+        }
+        Ok(())
+    }
+
+    fn end_chat(&mut self) -> anyhow::Result<()> {
+        if let Some(current) = self.current.take() {
+            self.chats.push(current.finish());
+        }
+        Ok(())
+    }
+}
+
+/// Второй проход потокового разбора: подставляет в готовую статистику
+/// сообщения, которые сборщики запомнили только по id. Нужен, только если
+/// такое сообщение оказалось старше окна последних сообщений сборщика.
+pub struct MessageResolver<'r> {
+    chats: Vec<&'r mut AllStats>,
+    current: Option<usize>,
+}
+
+impl<'r> MessageResolver<'r> {
+    pub fn new(report: &'r mut StatsReport) -> Self {
+        let chats = match report {
+            StatsReport::Chat(stats) => vec![stats.as_mut()],
+            StatsReport::Account(export) => export.chats.iter_mut().collect(),
+        };
+
+        Self {
+            chats,
+            current: None,
+        }
+    }
+
+    pub fn is_needed(&self) -> bool {
+        self.chats.iter().any(|stats| !stats.replies.is_resolved())
+    }
+}
+
+impl ChatSink for MessageResolver<'_> {
+    fn start_chat(&mut self, header: ChatHeader) -> anyhow::Result<()> {
+        self.current = self
+            .chats
+            .iter()
+            .position(|stats| stats.chat_id == header.id && !stats.replies.is_resolved());
+        Ok(())
+    }
+
+    fn message(&mut self, message: Message) -> anyhow::Result<()> {
+        if let Some(current) = self.current {
+            self.chats[current].replies.resolve(&message);
+        }
+        Ok(())
+    }
+
+    fn end_chat(&mut self) -> anyhow::Result<()> {
+        self.current = None;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::types::date_range::DateRange;
+    use crate::domain::types::export::ExportFile;
+    use crate::infrastructure::repositories::statistic_repository::StatisticRepository;
+    use crate::ingest::message_stream::ExportSeed;
+    use chrono::{TimeDelta, TimeZone, Utc};
+    use chrono_tz::Tz;
+    use serde::de::DeserializeSeed;
+
+    fn message(id: i64, from_id: &str, date: &str, unixtime: i64, extra: &str) -> String {
+        format!(
+            r#"{{"id": {id}, "type": "message", "date": "{date}", "date_unixtime": "{unixtime}",
+            "from": "{from_id}", "from_id": "{from_id}", "text": "hi 😀 there",
+            "text_entities": [{{"type": "plain", "text": "hi 😀 there"}}]{extra}}}"#
+        )
+    }
+
+    /// Экспорт аккаунта: личный чат в `chats` и группа в `left_chats`, часть
+    /// сообщений раньше начала периода.
+    fn account_export() -> String {
+        let personal = [
+            message(1, "user111", "2024-09-30T12:00:00", 1727697600, ""),
+            message(2, "user111", "2024-10-01T12:00:00", 1727784000, ""),
+            message(3, "user222", "2024-10-01T12:05:00", 1727784300, r#", "reply_to_message_id": 2"#),
+            message(4, "user111", "2024-10-01T12:06:00", 1727784360, r#", "reply_to_message_id": 3, "edited": "2024-10-01T12:10:00", "edited_unixtime": "1727784600""#),
+            message(5, "user222", "2024-10-02T09:00:00", 1727859600, ""),
+        ]
+        .join(",");
+        let group = [
+            message(10, "user333", "2024-10-01T20:00:00", 1727812800, ""),
+            message(
+                11,
+                "user111",
+                "2024-10-01T20:01:00",
+                1727812860,
+                r#", "reply_to_message_id": 10"#,
+            ),
+        ]
+        .join(",");
+
+        format!(
+            r#"{{"about": "", "personal_information": {{"user_id": 111}},
+            "chats": {{"about": "", "list": [{{"name": "Bob", "type": "personal_chat", "id": 222,
+            "messages": [{personal}]}}]}},
+            "left_chats": {{"about": "", "list": [{{"name": "Group", "type": "private_group",
+            "id": 333, "messages": [{group}]}}]}}}}"#
+        )
+    }
+
+    fn options() -> StatsOptions {
+        StatsOptions {
+            range: DateRange {
+                from: Some(Utc.with_ymd_and_hms(2024, 10, 1, 0, 0, 0).unwrap()),
+                to: None,
+                timezone: Tz::UTC,
+            },
+            max_reply_gap: TimeDelta::hours(6),
+            conversation_gap: TimeDelta::minutes(15),
+            top_n: 10,
+        }
+    }
+
+    #[tokio::test]
+    async fn streaming_matches_in_memory_stats() {
+        let json = account_export();
+
+        let mut collector = StatsCollector::new(options(), ".", None, ChatSelection::default());
+        let mut deserializer = serde_json::Deserializer::from_str(&json);
+        ExportSeed {
+            sink: &mut collector,
+        }
+        .deserialize(&mut deserializer)
+        .unwrap();
+        let streamed = collector.finish().unwrap();
+
+        let ExportFile::Account(export) = serde_json::from_str(&json).unwrap() else {
+            panic!("expected an account export");
+        };
+        let owner_id = export.owner_id().unwrap();
+        let mut chats = export.into_chats(&ChatSelection::default());
+        for chat in chats.iter_mut() {
+            chat.retain_by_date(&options().range).await;
+        }
+        let loaded = ExportStats::get_stats((&chats, options(), ".", &owner_id))
+            .await
+            .unwrap();
+
+        let StatsReport::Account(ref account) = streamed else {
+            panic!("expected account stats");
+        };
+        assert_eq!(account.owner_id, "user111");
+        assert_eq!(account.chats.len(), 2);
+        // Первое сообщение личного чата раньше периода
+        assert_eq!(account.chats[0].participants["user111"].messages_count, 2);
+        assert_eq!(account.chats[1].chat_id, 333);
+        assert_eq!(
+            serde_json::to_value(&streamed).unwrap(),
+            serde_json::to_value(StatsReport::Account(loaded)).unwrap()
+        );
+    }
+}
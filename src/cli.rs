@@ -0,0 +1,236 @@
+use crate::config::{AppConfig, DatabaseConfig};
+use clap::{Args, Parser, Subcommand, ValueEnum};
+
+/// Статистика по экспорту Telegram. Параметры, не переданные флагами,
+/// берутся из переменных окружения `APP_*` и `DATABASE_*` (или `.env`).
+#[derive(Parser)]
+#[command(name = "telegram_stats", version, about)]
+pub struct Cli {
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Посчитать статистику и сохранить её в JSON (команда по умолчанию)
+    Stats(Box<StatsArgs>),
+    /// Загрузить экспорт в Postgres
+    Import(ImportArgs),
+    /// Показать чаты в экспорте
+    Inspect(InspectArgs),
+    /// Сохранить разобранный экспорт (в том числе HTML и zip) в JSON
+    Export(ExportArgs),
+    /// Раздавать готовую статистику и медиафайлы по HTTP
+    Serve(ServeArgs),
+}
+
+#[derive(Args, Default)]
+pub struct InputArgs {
+    /// Файл `result.json`, zip-архив или каталог HTML-экспорта
+    #[arg(short, long)]
+    pub input: Option<String>,
+}
+
+#[derive(Args, Default)]
+pub struct ChatArgs {
+    /// Id чатов экспорта аккаунта, через запятую
+    #[arg(long = "chat-id", value_delimiter = ',')]
+    pub chat_ids: Vec<i64>,
+    /// Названия чатов, через запятую
+    #[arg(long = "chat-name", value_delimiter = ',')]
+    pub chat_names: Vec<String>,
+    /// Типы чатов (`personal_chat`, `private_group`, ...), через запятую
+    #[arg(long = "chat-type", value_delimiter = ',')]
+    pub chat_types: Vec<String>,
+}
+
+#[derive(Args, Default)]
+pub struct OutputArgs {
+    /// Куда сохранить JSON
+    #[arg(short, long)]
+    pub output: Option<String>,
+    /// Форматирование JSON
+    #[arg(long, value_enum, default_value_t)]
+    pub format: OutputFormat,
+}
+
+#[derive(Clone, Copy, Default, ValueEnum)]
+pub enum OutputFormat {
+    #[default]
+    Pretty,
+    Compact,
+}
+
+impl OutputFormat {
+    pub fn is_pretty(self) -> bool {
+        matches!(self, OutputFormat::Pretty)
+    }
+}
+
+#[derive(Args, Default)]
+pub struct StatsArgs {
+    #[command(flatten)]
+    pub input: InputArgs,
+    #[command(flatten)]
+    pub output: OutputArgs,
+    #[command(flatten)]
+    pub chats: ChatArgs,
+    /// Год, за который считается статистика
+    #[arg(short, long, conflicts_with_all = ["range", "from", "to"])]
+    pub year: Option<i32>,
+    /// Период: `2023-06..2024-06`, `2024`, `last 30 days`, `all`
+    #[arg(short, long, conflicts_with_all = ["from", "to"])]
+    pub range: Option<String>,
+    /// Начало периода: `2024`, `2024-06` или `2024-06-15`
+    #[arg(long)]
+    pub from: Option<String>,
+    /// Конец периода включительно: `2024`, `2024-06` или `2024-06-15`
+    #[arg(long)]
+    pub to: Option<String>,
+    /// IANA-часовой пояс для разбивки по дням, например `Europe/Moscow`
+    #[arg(long)]
+    pub timezone: Option<String>,
+    /// Пауза в минутах, после которой сообщение не считается ответом
+    #[arg(long = "max-reply-gap")]
+    pub max_reply_gap_minutes: Option<i64>,
+    /// Пауза в минутах, после которой начинается новый разговор
+    #[arg(long = "conversation-gap")]
+    pub conversation_gap_minutes: Option<i64>,
+    /// Размер топов (разговоры, стикеры, эмодзи)
+    #[arg(long = "top")]
+    pub top_n: Option<usize>,
+    /// `from_id` владельца экспорта, например `user123456`
+    #[arg(long)]
+    pub owner: Option<String>,
+    /// Разбирать экспорт потоком, не загружая его целиком в память
+    #[arg(long)]
+    pub streaming: bool,
+    /// Куда распаковать медиафайлы из zip-экспорта
+    #[arg(long)]
+    pub media_dir: Option<String>,
+}
+
+#[derive(Args)]
+pub struct ImportArgs {
+    #[command(flatten)]
+    pub input: InputArgs,
+    #[command(flatten)]
+    pub chats: ChatArgs,
+    /// Строка подключения к Postgres
+    #[arg(long)]
+    pub database_url: Option<String>,
+}
+
+#[derive(Args)]
+pub struct InspectArgs {
+    #[command(flatten)]
+    pub input: InputArgs,
+}
+
+#[derive(Args)]
+pub struct ExportArgs {
+    #[command(flatten)]
+    pub input: InputArgs,
+    #[command(flatten)]
+    pub output: OutputArgs,
+    #[command(flatten)]
+    pub chats: ChatArgs,
+}
+
+#[derive(Args)]
+pub struct ServeArgs {
+    /// Файл статистики, по умолчанию `APP_OUTPUT_PATH`
+    #[arg(short, long)]
+    pub stats: Option<String>,
+    /// Каталог с медиафайлами, по умолчанию `source_dir` из статистики
+    #[arg(long)]
+    pub media_dir: Option<String>,
+    /// Адрес для прослушивания
+    #[arg(long, default_value = "127.0.0.1:8080")]
+    pub listen: String,
+}
+
+impl InputArgs {
+    pub fn apply(self, config: &mut AppConfig) {
+        if self.input.is_some() {
+            config.input_path = self.input;
+        }
+    }
+}
+
+impl ChatArgs {
+    pub fn apply(self, config: &mut AppConfig) {
+        if !self.chat_ids.is_empty() {
+            config.chat_ids = self.chat_ids;
+        }
+        if !self.chat_names.is_empty() {
+            config.chat_names = self.chat_names;
+        }
+        if !self.chat_types.is_empty() {
+            config.chat_types = self.chat_types;
+        }
+    }
+}
+
+impl OutputArgs {
+    pub fn apply(self, config: &mut AppConfig) -> OutputFormat {
+        if self.output.is_some() {
+            config.output_path = self.output;
+        }
+        self.format
+    }
+}
+
+impl StatsArgs {
+    pub fn apply(self, config: &mut AppConfig) -> OutputFormat {
+        self.input.apply(config);
+        self.chats.apply(config);
+        // Период из командной строки целиком заменяет период из окружения
+        if self.year.is_some() || self.range.is_some() || self.from.is_some() || self.to.is_some() {
+            config.year = self.year;
+            config.range = self.range;
+            config.from = self.from;
+            config.to = self.to;
+        }
+        if self.timezone.is_some() {
+            config.timezone = self.timezone;
+        }
+        if let Some(minutes) = self.max_reply_gap_minutes {
+            config.max_reply_gap_minutes = minutes;
+        }
+        if let Some(minutes) = self.conversation_gap_minutes {
+            config.conversation_gap_minutes = minutes;
+        }
+        if let Some(top_n) = self.top_n {
+            config.top_n = top_n;
+        }
+        if self.owner.is_some() {
+            config.owner_id = self.owner;
+        }
+        if self.streaming {
+            config.streaming = true;
+        }
+        if self.media_dir.is_some() {
+            config.media_dir = self.media_dir;
+        }
+        self.output.apply(config)
+    }
+}
+
+impl ImportArgs {
+    pub fn apply(self, config: &mut AppConfig, database_config: &mut DatabaseConfig) {
+        self.input.apply(config);
+        self.chats.apply(config);
+        if self.database_url.is_some() {
+            database_config.url = self.database_url;
+        }
+    }
+}
+
+impl ExportArgs {
+    pub fn apply(self, config: &mut AppConfig) -> OutputFormat {
+        self.input.apply(config);
+        self.chats.apply(config);
+        self.output.apply(config)
+    }
+}
@@ -1,29 +1,178 @@
+use crate::domain::types::date_range::DateRange;
+use crate::domain::types::export::ChatSelection;
+use crate::domain::types::options::StatsOptions;
+use anyhow::Context;
+use chrono::{TimeDelta, Utc};
+use chrono_tz::Tz;
 use serde::Deserialize;
 
 pub struct Config {
-    #[allow(dead_code)]
     pub database_config: DatabaseConfig,
     pub app_config: AppConfig,
 }
 
 impl Config {
+    /// Значения по умолчанию из окружения (и `.env`), которые можно
+    /// переопределить аргументами командной строки.
     pub fn from_env() -> anyhow::Result<Self> {
         Ok(Self {
-            database_config: envy::prefixed("DATABASE_").from_env()?,
-            app_config: envy::prefixed("APP_").from_env()?,
+            database_config: envy::prefixed("DATABASE_")
+                .from_env()
+                .context("Invalid DATABASE_* environment variables")?,
+            app_config: envy::prefixed("APP_")
+                .from_env()
+                .context("Invalid APP_* environment variables")?,
         })
     }
 }
 
 #[derive(Deserialize)]
 pub struct DatabaseConfig {
-    #[allow(dead_code)]
-    pub url: String,
+    pub url: Option<String>,
+}
+
+impl DatabaseConfig {
+    pub fn url(&self) -> anyhow::Result<&str> {
+        self.url
+            .as_deref()
+            .context("Database URL is not set, pass --database-url or DATABASE_URL")
+    }
 }
 
 #[derive(Deserialize)]
 pub struct AppConfig {
-    pub input_path: String,
-    pub output_path: String,
-    pub year: i32,
+    pub input_path: Option<String>,
+    pub output_path: Option<String>,
+    /// Период статистики: `APP_RANGE` (`2023-06..2024-06`, `last 30 days`,
+    /// `all`), либо границы `APP_FROM`/`APP_TO` (включительно), либо `APP_YEAR`.
+    /// Без них статистика считается за всё время.
+    pub year: Option<i32>,
+    pub range: Option<String>,
+    pub from: Option<String>,
+    pub to: Option<String>,
+    /// IANA-часовой пояс (`Europe/Moscow`) для разбивки по дням и часам.
+    /// По умолчанию системный.
+    pub timezone: Option<String>,
+    /// Пауза в минутах, после которой следующее сообщение не считается ответом.
+    #[serde(default = "default_max_reply_gap_minutes")]
+    pub max_reply_gap_minutes: i64,
+    /// Пауза в минутах, после которой начинается новый разговор.
+    #[serde(default = "default_conversation_gap_minutes")]
+    pub conversation_gap_minutes: i64,
+    /// Размер топов (разговоры, стикеры, эмодзи).
+    #[serde(default = "default_top_n")]
+    pub top_n: usize,
+    /// `from_id` владельца экспорта (например, `user123456`). Если не задан,
+    /// определяется автоматически по чату.
+    pub owner_id: Option<String>,
+    /// Фильтры чатов для экспорта всего аккаунта, значения через запятую.
+    #[serde(default)]
+    pub chat_ids: Vec<i64>,
+    #[serde(default)]
+    pub chat_names: Vec<String>,
+    #[serde(default)]
+    pub chat_types: Vec<String>,
+    /// Потоковый разбор без загрузки всего экспорта в память. Сообщения
+    /// должны идти в хронологическом порядке, как в выгрузке Telegram.
+    #[serde(default)]
+    pub streaming: bool,
+    /// Куда распаковать медиафайлы из zip-экспорта. По умолчанию `media`
+    /// рядом с файлом статистики.
+    pub media_dir: Option<String>,
+}
+
+impl AppConfig {
+    pub fn input_path(&self) -> anyhow::Result<&str> {
+        self.input_path
+            .as_deref()
+            .context("Input path is not set, pass --input or APP_INPUT_PATH")
+    }
+
+    pub fn output_path(&self) -> anyhow::Result<&str> {
+        self.output_path
+            .as_deref()
+            .context("Output path is not set, pass --output or APP_OUTPUT_PATH")
+    }
+
+    pub fn timezone(&self) -> anyhow::Result<Tz> {
+        match &self.timezone {
+            Some(timezone) => timezone.parse().map_err(|_| {
+                anyhow::anyhow!("Unknown timezone `{timezone}`, expected e.g. `Europe/Moscow`")
+            }),
+            None => Ok(iana_time_zone::get_timezone()
+                .ok()
+                .and_then(|timezone| timezone.parse().ok())
+                .unwrap_or(Tz::UTC)),
+        }
+    }
+
+    pub fn date_range(&self) -> anyhow::Result<DateRange> {
+        let timezone = self.timezone()?;
+        let now = Utc::now();
+
+        if let Some(range) = &self.range {
+            return Ok(DateRange::parse(range, now, timezone)?);
+        }
+
+        if self.from.is_some() || self.to.is_some() {
+            let range = format!(
+                "{}..{}",
+                self.from.as_deref().unwrap_or_default(),
+                self.to.as_deref().unwrap_or_default()
+            );
+            return Ok(DateRange::parse(&range, now, timezone)?);
+        }
+
+        match self.year {
+            Some(year) => Ok(DateRange::year(year, timezone)?),
+            None => Ok(DateRange::all_time(timezone)),
+        }
+    }
+
+    pub fn media_dir(&self) -> anyhow::Result<String> {
+        match &self.media_dir {
+            Some(media_dir) => Ok(media_dir.clone()),
+            None => Ok(std::path::Path::new(self.output_path()?)
+                .with_file_name("media")
+                .to_string_lossy()
+                .into_owned()),
+        }
+    }
+
+    pub fn stats_options(&self) -> anyhow::Result<StatsOptions> {
+        Ok(StatsOptions {
+            range: self.date_range()?,
+            max_reply_gap: Self::gap(self.max_reply_gap_minutes, "max reply gap")?,
+            conversation_gap: Self::gap(self.conversation_gap_minutes, "conversation gap")?,
+            top_n: self.top_n,
+        })
+    }
+
+    fn gap(minutes: i64, name: &str) -> anyhow::Result<TimeDelta> {
+        TimeDelta::try_minutes(minutes)
+            .filter(|gap| *gap > TimeDelta::zero())
+            .with_context(|| {
+                format!("Invalid {name} `{minutes}`, expected a positive number of minutes")
+            })
+    }
+
+    pub fn chat_selection(&self) -> ChatSelection {
+        ChatSelection {
+            ids: self.chat_ids.clone(),
+            names: self.chat_names.clone(),
+            types: self.chat_types.clone(),
+        }
+    }
+}
+
+fn default_max_reply_gap_minutes() -> i64 {
+    6 * 60
+}
+
+fn default_conversation_gap_minutes() -> i64 {
+    15
+}
+
+fn default_top_n() -> usize {
+    5
 }
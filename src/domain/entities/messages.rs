@@ -129,6 +129,8 @@ pub struct Model {
     pub via_giveaway: Option<bool>,
     #[sea_orm(column_type = "JsonBinary", nullable)]
     pub giveaway_information: Option<Json>,
+    #[sea_orm(column_type = "JsonBinary", nullable)]
+    pub reactions: Option<Json>,
 }
 
 #[derive(Copy, Clone, Debug, EnumIter, DeriveRelation)]
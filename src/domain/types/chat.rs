@@ -1,23 +1,30 @@
-use crate::utils::deserialize_datetime::deserialize_datetime;
+use crate::utils::deserialize_datetime::{deserialize_datetime, serialize_datetime};
 use chrono::DateTime;
 use serde::{Deserialize, Serialize};
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Chat {
     pub id: i64,
-    pub name: String,
+    pub name: Option<String>,
     pub r#type: String,
     pub messages: Vec<Message>,
 }
 
-#[derive(Clone, Debug, Serialize, Deserialize)]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
 pub struct Message {
     pub id: i64,
     pub r#type: String,
     pub action: Option<String>,
-    #[serde(deserialize_with = "deserialize_datetime")]
+    /// Момент отправки из `date_unixtime`.
+    #[serde(
+        rename = "date_unixtime",
+        deserialize_with = "deserialize_datetime",
+        serialize_with = "serialize_datetime"
+    )]
     pub date: DateTime<chrono::Utc>,
-    pub date_unixtime: String,
+    /// Поле `date` экспорта: время без часового пояса, в котором был сделан экспорт.
+    #[serde(rename = "date")]
+    pub local_date: String,
     pub from: Option<String>,
     pub from_id: Option<String>,
     pub edited: Option<String>,
@@ -68,6 +75,7 @@ pub struct Message {
     pub saved_from: Option<String>,
     pub via_bot: Option<String>,
     pub media_type: Option<String>,
+    pub sticker_emoji: Option<String>,
     pub performer: Option<String>,
     pub mime_type: Option<String>,
     pub contact_information: Option<Contact>,
@@ -86,6 +94,7 @@ pub struct Message {
     pub unclaimed: Option<bool>,
     pub via_giveaway: Option<bool>,
     pub giveaway_information: Option<Giveaway>,
+    pub reactions: Option<Vec<Reaction>>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -95,6 +104,12 @@ pub enum MessageText {
     Entities(Vec<TextEntity>),
 }
 
+impl Default for MessageText {
+    fn default() -> Self {
+        MessageText::Plain(String::new())
+    }
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 #[serde(untagged)]
 pub enum TextEntity {
@@ -106,6 +121,12 @@ pub enum TextEntity {
 pub struct MessageEntity {
     pub r#type: String,
     pub text: String,
+    /// Адрес ссылки `text_link`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub href: Option<String>,
+    /// Упомянутый пользователь `mention_name`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub user_id: Option<i64>,
 }
 
 #[derive(Clone, Debug, Serialize, Deserialize)]
@@ -147,6 +168,26 @@ pub struct PollAnswer {
     pub chosen: bool,
 }
 
+/// Реакция на сообщение: обычный эмодзи (`emoji`) или кастомный
+/// (`custom_emoji`, `document_id`). В `recent` экспорт пишет только
+/// последних поставивших реакцию.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Reaction {
+    pub r#type: String,
+    pub count: i32,
+    pub emoji: Option<String>,
+    pub document_id: Option<String>,
+    #[serde(default)]
+    pub recent: Vec<ReactionSender>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReactionSender {
+    pub from: Option<String>,
+    pub from_id: Option<String>,
+    pub date: Option<String>,
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Giveaway {
     pub quantity: i32,
@@ -154,3 +195,31 @@ pub struct Giveaway {
     pub until_date: String,
     pub channels: Vec<i32>,
 }
+
+/// Владелец экспорта. Для личного чата без явного владельца им считается любой
+/// отправитель, кроме собеседника (`from_id` собеседника равен `user{chat.id}`).
+#[derive(Clone, Debug)]
+pub enum ChatOwner {
+    Id(String),
+    NotPeer(String),
+}
+
+impl ChatOwner {
+    pub fn is_owner(&self, message: &Message) -> bool {
+        message
+            .from_id
+            .as_deref()
+            .is_some_and(|id| self.is_owner_id(id))
+    }
+
+    pub fn is_owner_id(&self, id: &str) -> bool {
+        match self {
+            ChatOwner::Id(owner_id) => id == owner_id,
+            ChatOwner::NotPeer(peer_id) => id != peer_id,
+        }
+    }
+
+    pub fn is_member(&self, message: &Message) -> bool {
+        message.from_id.is_some() && !self.is_owner(message)
+    }
+}
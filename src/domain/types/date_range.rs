@@ -0,0 +1,336 @@
+use crate::domain::types::stats::Period;
+use chrono::{DateTime, Datelike, Duration, Months, NaiveDate, NaiveTime, TimeZone, Utc};
+use chrono_tz::Tz;
+
+/// Период, за который считается статистика: `[from, to)`. Незаданная граница
+/// означает, что период с этой стороны не ограничен. Границы дней, месяцев
+/// и лет считаются в часовом поясе `timezone`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct DateRange {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub timezone: Tz,
+}
+
+impl DateRange {
+    pub fn all_time(timezone: Tz) -> Self {
+        Self {
+            from: None,
+            to: None,
+            timezone,
+        }
+    }
+
+    pub fn year(year: i32, timezone: Tz) -> Result<Self, DateRangeError> {
+        let invalid = || DateRangeError::Invalid(year.to_string());
+        let from = NaiveDate::from_ymd_opt(year, 1, 1).ok_or_else(invalid)?;
+        let to = NaiveDate::from_ymd_opt(year + 1, 1, 1).ok_or_else(invalid)?;
+
+        Ok(Self {
+            from: Some(Self::midnight(from, timezone)),
+            to: Some(Self::midnight(to, timezone)),
+            timezone,
+        })
+    }
+
+    /// Разбирает период относительно `now`. Поддерживаются:
+    /// - `all`, `all time` — без ограничений;
+    /// - `2024`, `2024-06`, `2024-06-15` — год, месяц или день целиком;
+    /// - `2023-06..2024-06`, `2024..`, `..2024-06-15` — диапазон, обе границы
+    ///   включаются целиком, пустая граница не ограничена;
+    /// - `last 30 days`, `last week`, `last 6 months`, `last year` — последние
+    ///   N дней, недель, месяцев или лет, включая сегодняшний день.
+    pub fn parse(value: &str, now: DateTime<Utc>, timezone: Tz) -> Result<Self, DateRangeError> {
+        let value = value.trim().to_lowercase();
+        let invalid = || DateRangeError::Invalid(value.clone());
+
+        if matches!(value.as_str(), "" | "all" | "all time" | "all-time") {
+            return Ok(Self::all_time(timezone));
+        }
+
+        if let Some(relative) = value.strip_prefix("last ") {
+            let tomorrow = now.with_timezone(&timezone).date_naive() + Duration::days(1);
+            let from = Self::subtract(tomorrow, relative).ok_or_else(invalid)?;
+            return Ok(Self {
+                from: Some(Self::midnight(from, timezone)),
+                to: Some(Self::midnight(tomorrow, timezone)),
+                timezone,
+            });
+        }
+
+        let (from, to) = match value.split_once("..") {
+            Some((from, to)) => (from.trim(), to.trim()),
+            None => (value.as_str(), value.as_str()),
+        };
+        let from = match from {
+            "" => None,
+            from => Some(Self::unit(from).ok_or_else(invalid)?.0),
+        };
+        let to = match to {
+            "" => None,
+            to => Some(Self::unit(to).ok_or_else(invalid)?.1),
+        };
+
+        if let (Some(from), Some(to)) = (from, to) {
+            if from >= to {
+                return Err(invalid());
+            }
+        }
+
+        Ok(Self {
+            from: from.map(|from| Self::midnight(from, timezone)),
+            to: to.map(|to| Self::midnight(to, timezone)),
+            timezone,
+        })
+    }
+
+    pub fn contains(&self, date: DateTime<Utc>) -> bool {
+        self.from.is_none_or(|from| date >= from) && self.to.is_none_or(|to| date < to)
+    }
+
+    /// Год, если период совпадает с календарным годом.
+    pub fn calendar_year(&self) -> Option<i32> {
+        let year = self.from?.with_timezone(&self.timezone).year();
+        (Self::year(year, self.timezone).ok()? == *self).then_some(year)
+    }
+
+    /// Фактический период статистики: открытые границы заменяются днями
+    /// первого и последнего сообщения, а конец, ещё не наступивший к последнему
+    /// сообщению (например, у текущего года), обрезается концом его дня.
+    pub fn period(&self, first: Option<DateTime<Utc>>, last: Option<DateTime<Utc>>) -> Period {
+        let from = self
+            .from
+            .or_else(|| first.map(|first| Self::midnight(self.local_date(first), self.timezone)));
+        let last = last
+            .map(|last| Self::midnight(self.local_date(last) + Duration::days(1), self.timezone));
+        let to = match (self.to, last) {
+            (Some(to), Some(last)) => Some(to.min(last)),
+            (to, last) => to.or(last),
+        };
+
+        // Календарные дни, а не сутки: переход на зимнее время удлиняет день
+        let days = match (from, to) {
+            (Some(from), Some(to)) if to > from => {
+                (self.local_date(to) - self.local_date(from)).num_days()
+            }
+            _ => 0,
+        };
+
+        Period {
+            from,
+            to,
+            days,
+            timezone: self.timezone.name().to_string(),
+        }
+    }
+
+    /// Календарный день в часовом поясе периода.
+    fn local_date(&self, date: DateTime<Utc>) -> NaiveDate {
+        date.with_timezone(&self.timezone).date_naive()
+    }
+
+    /// Начало и конец (не включительно) года, месяца или дня.
+    fn unit(value: &str) -> Option<(NaiveDate, NaiveDate)> {
+        let parts: Vec<&str> = value.split('-').collect();
+        let year: i32 = parts.first()?.parse().ok()?;
+
+        match parts[1..] {
+            [] => Some((
+                NaiveDate::from_ymd_opt(year, 1, 1)?,
+                NaiveDate::from_ymd_opt(year + 1, 1, 1)?,
+            )),
+            [month] => {
+                let start = NaiveDate::from_ymd_opt(year, month.parse().ok()?, 1)?;
+                Some((start, start.checked_add_months(Months::new(1))?))
+            }
+            [month, day] => {
+                let start = NaiveDate::from_ymd_opt(year, month.parse().ok()?, day.parse().ok()?)?;
+                Some((start, start + Duration::days(1)))
+            }
+            _ => None,
+        }
+    }
+
+    /// `date` минус «N дней/недель/месяцев/лет»; без числа N равно 1.
+    fn subtract(date: NaiveDate, relative: &str) -> Option<NaiveDate> {
+        let mut words = relative.split_whitespace();
+        let (count, unit) = match (words.next()?, words.next(), words.next()) {
+            (count, Some(unit), None) => (count.parse::<u32>().ok()?, unit),
+            (unit, None, None) => (1, unit),
+            _ => return None,
+        };
+        if count == 0 {
+            return None;
+        }
+
+        match unit.trim_end_matches('s') {
+            "day" => date.checked_sub_signed(Duration::days(count.into())),
+            "week" => date.checked_sub_signed(Duration::weeks(count.into())),
+            "month" => date.checked_sub_months(Months::new(count)),
+            "year" => date.checked_sub_months(Months::new(count.checked_mul(12)?)),
+            _ => None,
+        }
+    }
+
+    fn midnight(date: NaiveDate, timezone: Tz) -> DateTime<Utc> {
+        let midnight = date.and_time(NaiveTime::MIN);
+        timezone
+            .from_local_datetime(&midnight)
+            .earliest()
+            // Полночь может выпасть на переход на летнее время: тогда день
+            // начинается в момент перехода, то есть в полночь по старому смещению
+            .or_else(|| {
+                let before = timezone.from_local_datetime(&(midnight - Duration::hours(1)));
+                before.earliest().map(|before| before + Duration::hours(1))
+            })
+            .unwrap_or_else(|| timezone.from_utc_datetime(&midnight))
+            .with_timezone(&Utc)
+    }
+}
+
+#[derive(Debug, thiserror::Error)]
+pub enum DateRangeError {
+    #[error("Invalid date range `{0}`, expected e.g. `2024`, `2023-06..2024-06`, `last 30 days` or `all`")]
+    Invalid(String),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono_tz::America::Santiago;
+    use chrono_tz::Europe::Berlin;
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2024, 11, 15, 12, 0, 0).unwrap()
+    }
+
+    fn day(year: i32, month: u32, day: u32) -> Option<DateTime<Utc>> {
+        let date = NaiveDate::from_ymd_opt(year, month, day).unwrap();
+        Some(DateRange::midnight(date, Berlin))
+    }
+
+    fn bounds(value: &str) -> (Option<DateTime<Utc>>, Option<DateTime<Utc>>) {
+        let range = DateRange::parse(value, now(), Berlin).unwrap();
+        assert_eq!(range.timezone, Berlin);
+        (range.from, range.to)
+    }
+
+    #[test]
+    fn parse_all_time() {
+        for value in ["", "all", "All Time", "all-time"] {
+            assert_eq!(bounds(value), (None, None), "{value}");
+        }
+    }
+
+    #[test]
+    fn parse_calendar_units() {
+        assert_eq!(bounds("2024"), (day(2024, 1, 1), day(2025, 1, 1)));
+        assert_eq!(bounds("2024-02"), (day(2024, 2, 1), day(2024, 3, 1)));
+        assert_eq!(bounds("2024-12"), (day(2024, 12, 1), day(2025, 1, 1)));
+        assert_eq!(bounds("2024-06-15"), (day(2024, 6, 15), day(2024, 6, 16)));
+    }
+
+    #[test]
+    fn parse_ranges() {
+        assert_eq!(
+            bounds("2023-06..2024-06"),
+            (day(2023, 6, 1), day(2024, 7, 1))
+        );
+        assert_eq!(bounds("2024 .. 2024"), (day(2024, 1, 1), day(2025, 1, 1)));
+        assert_eq!(bounds("2024.."), (day(2024, 1, 1), None));
+        assert_eq!(bounds("..2024-06-15"), (None, day(2024, 6, 16)));
+    }
+
+    #[test]
+    fn parse_relative_periods() {
+        // Сегодня 15 ноября по Берлину, период заканчивается началом 16-го
+        assert_eq!(
+            bounds("last 30 days"),
+            (day(2024, 10, 17), day(2024, 11, 16))
+        );
+        assert_eq!(bounds("last day"), (day(2024, 11, 15), day(2024, 11, 16)));
+        assert_eq!(bounds("last week"), (day(2024, 11, 9), day(2024, 11, 16)));
+        assert_eq!(
+            bounds("last 2 weeks"),
+            (day(2024, 11, 2), day(2024, 11, 16))
+        );
+        assert_eq!(
+            bounds("last 6 months"),
+            (day(2024, 5, 16), day(2024, 11, 16))
+        );
+        assert_eq!(bounds("last year"), (day(2023, 11, 16), day(2024, 11, 16)));
+    }
+
+    #[test]
+    fn parse_rejects_invalid_values() {
+        for value in [
+            "garbage",
+            "2024-13",
+            "2024-02-30",
+            "2024-06-15-01",
+            "2024..2023",
+            "2024-06..2024-05-31",
+            "last 0 days",
+            "last -1 days",
+            "last 3 fortnights",
+            "last 3 days ago",
+        ] {
+            assert!(
+                matches!(
+                    DateRange::parse(value, now(), Berlin),
+                    Err(DateRangeError::Invalid(_))
+                ),
+                "{value}"
+            );
+        }
+    }
+
+    #[test]
+    fn midnight_in_dst_gap_starts_at_transition() {
+        // В Сантьяго 8 сентября 2024 часы переводятся с 00:00 сразу на 01:00
+        let date = NaiveDate::from_ymd_opt(2024, 9, 8).unwrap();
+        let start = DateRange::midnight(date, Santiago);
+
+        assert_eq!(start, Utc.with_ymd_and_hms(2024, 9, 8, 4, 0, 0).unwrap());
+        assert_eq!(
+            start.with_timezone(&Santiago).naive_local(),
+            date.and_hms_opt(1, 0, 0).unwrap()
+        );
+    }
+
+    #[test]
+    fn period_counts_calendar_days_across_dst() {
+        let october = DateRange::parse("2024-10", now(), Berlin).unwrap();
+        assert_eq!(october.period(None, None).days, 31);
+
+        let march = DateRange::parse("2024-03", now(), Berlin).unwrap();
+        assert_eq!(march.period(None, None).days, 31);
+    }
+
+    #[test]
+    fn period_ends_with_last_message_day() {
+        let year = DateRange::parse("2026", now(), Berlin).unwrap();
+        let first = Utc.with_ymd_and_hms(2026, 1, 3, 10, 0, 0).unwrap();
+        let last = Utc.with_ymd_and_hms(2026, 10, 17, 22, 30, 0).unwrap();
+
+        // 17 октября 22:30 UTC в Берлине уже 18-е
+        let period = year.period(Some(first), Some(last));
+        assert_eq!(period.from, day(2026, 1, 1));
+        assert_eq!(period.to, day(2026, 10, 19));
+        assert_eq!(period.days, 291);
+
+        let past = DateRange::parse("2024-10", now(), Berlin).unwrap();
+        let last = Utc.with_ymd_and_hms(2024, 11, 2, 10, 0, 0).unwrap();
+        assert_eq!(past.period(None, Some(last)).days, 31);
+    }
+
+    #[test]
+    fn period_without_bounds_uses_message_days() {
+        let range = DateRange::all_time(Berlin);
+        let first = Utc.with_ymd_and_hms(2024, 10, 26, 21, 30, 0).unwrap();
+        let last = Utc.with_ymd_and_hms(2024, 10, 27, 23, 30, 0).unwrap();
+
+        // 26, 27 и 28 октября по Берлину, 27-го переход на зимнее время
+        assert_eq!(range.period(Some(first), Some(last)).days, 3);
+    }
+}
@@ -0,0 +1,197 @@
+use crate::domain::types::chat::{Chat, Contact};
+use serde::de::{Error as SerdeError, IgnoredAny, MapAccess, Visitor};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::fmt;
+
+/// Содержимое `result.json`: либо экспорт одного чата, либо экспорт всего аккаунта.
+#[derive(Clone, Debug, Serialize)]
+#[serde(untagged)]
+pub enum ExportFile {
+    Account(Export),
+    Chat(Chat),
+}
+
+/// Вид экспорта определяется по ключам верхнего уровня (`chats` у аккаунта,
+/// `messages` у чата) за один проход, без буферизации документа, поэтому
+/// ошибки разбора сохраняют строку и столбец.
+impl<'de> Deserialize<'de> for ExportFile {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        deserializer.deserialize_map(ExportFileVisitor)
+    }
+}
+
+struct ExportFileVisitor;
+
+impl<'de> Visitor<'de> for ExportFileVisitor {
+    type Value = ExportFile;
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("telegram export object")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<ExportFile, A::Error> {
+        let mut id = None;
+        let mut name = None;
+        let mut r#type = None;
+        let mut messages = None;
+        let mut personal_information = None;
+        let mut chats = None;
+        let mut left_chats = None;
+        let mut contacts = None;
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "id" => id = Some(map.next_value()?),
+                "name" => name = map.next_value()?,
+                "type" => r#type = Some(map.next_value()?),
+                "messages" => messages = Some(map.next_value()?),
+                "personal_information" => personal_information = map.next_value()?,
+                "chats" => chats = Some(map.next_value()?),
+                "left_chats" => left_chats = map.next_value()?,
+                "contacts" => contacts = map.next_value()?,
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+
+        if let Some(chats) = chats {
+            return Ok(ExportFile::Account(Export {
+                personal_information,
+                chats,
+                left_chats,
+                contacts,
+            }));
+        }
+
+        Ok(ExportFile::Chat(Chat {
+            id: id.ok_or_else(|| A::Error::missing_field("id"))?,
+            name,
+            r#type: r#type.ok_or_else(|| A::Error::missing_field("type"))?,
+            messages: messages.ok_or_else(|| A::Error::missing_field("messages"))?,
+        }))
+    }
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct Export {
+    pub personal_information: Option<PersonalInformation>,
+    pub chats: ChatList,
+    pub left_chats: Option<ChatList>,
+    pub contacts: Option<ContactList>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct PersonalInformation {
+    pub user_id: i64,
+    pub first_name: Option<String>,
+    pub last_name: Option<String>,
+    pub phone_number: Option<String>,
+    pub username: Option<String>,
+    pub bio: Option<String>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ChatList {
+    pub about: Option<String>,
+    pub list: Vec<Chat>,
+}
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ContactList {
+    pub about: Option<String>,
+    pub list: Vec<Contact>,
+}
+
+#[derive(Clone, Debug, Default)]
+pub struct ChatSelection {
+    pub ids: Vec<i64>,
+    pub names: Vec<String>,
+    pub types: Vec<String>,
+}
+
+impl ChatSelection {
+    /// Пустой критерий пропускает любой чат.
+    pub fn matches(&self, id: i64, name: Option<&str>, r#type: &str) -> bool {
+        (self.ids.is_empty() || self.ids.contains(&id))
+            && (self.names.is_empty()
+                || name.is_some_and(|name| self.names.iter().any(|item| item == name)))
+            && (self.types.is_empty() || self.types.iter().any(|item| item == r#type))
+    }
+}
+
+impl Export {
+    pub fn owner_id(&self) -> Option<String> {
+        self.personal_information
+            .as_ref()
+            .map(|info| format!("user{}", info.user_id))
+    }
+
+    /// Оставляет в экспорте только выбранные чаты.
+    pub fn retain_chats(&mut self, selection: &ChatSelection) {
+        let matches = |chat: &Chat| selection.matches(chat.id, chat.name.as_deref(), &chat.r#type);
+
+        self.chats.list.retain(matches);
+        if let Some(left_chats) = self.left_chats.as_mut() {
+            left_chats.list.retain(matches);
+        }
+    }
+
+    pub fn into_chats(self, selection: &ChatSelection) -> Vec<Chat> {
+        self.chats
+            .list
+            .into_iter()
+            .chain(self.left_chats.into_iter().flat_map(|chats| chats.list))
+            .filter(|chat| selection.matches(chat.id, chat.name.as_deref(), &chat.r#type))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MESSAGE: &str = r#"{"id": 1, "type": "message", "date": "2024-10-01T12:00:00",
+        "date_unixtime": "1727784000", "from": "Bob", "from_id": "user222", "text": "hi",
+        "text_entities": [{"type": "plain", "text": "hi"}]}"#;
+
+    #[test]
+    fn chat_export_is_detected_by_messages() {
+        let json = format!(
+            r#"{{"name": "Bob", "type": "personal_chat", "id": 222, "messages": [{MESSAGE}]}}"#
+        );
+
+        let ExportFile::Chat(chat) = serde_json::from_str(&json).unwrap() else {
+            panic!("expected a chat export");
+        };
+        assert_eq!(chat.id, 222);
+        assert_eq!(chat.name.as_deref(), Some("Bob"));
+        assert_eq!(chat.messages.len(), 1);
+    }
+
+    #[test]
+    fn account_export_is_detected_by_chats() {
+        let json = format!(
+            r#"{{"about": "", "personal_information": {{"user_id": 111}},
+            "chats": {{"list": [{{"name": "Bob", "type": "personal_chat", "id": 222,
+            "messages": [{MESSAGE}]}}]}}, "left_chats": {{"list": []}}}}"#
+        );
+
+        let ExportFile::Account(export) = serde_json::from_str(&json).unwrap() else {
+            panic!("expected an account export");
+        };
+        assert_eq!(export.owner_id().as_deref(), Some("user111"));
+        assert_eq!(export.chats.list.len(), 1);
+        assert_eq!(export.left_chats.map(|chats| chats.list.len()), Some(0));
+    }
+
+    #[test]
+    fn parse_error_keeps_location() {
+        let json = r#"{"name": "Bob", "type": "personal_chat", "id": 222,
+            "messages": [{"id": 1, "type": "message"}]}"#;
+
+        let error = serde_json::from_str::<ExportFile>(json).unwrap_err();
+        assert_eq!(error.line(), 2);
+        assert!(error.to_string().contains("missing field"), "{error}");
+    }
+}
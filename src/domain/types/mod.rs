@@ -1,2 +1,5 @@
 pub mod chat;
+pub mod date_range;
+pub mod export;
+pub mod options;
 pub mod stats;
@@ -0,0 +1,14 @@
+use crate::domain::types::date_range::DateRange;
+use chrono::TimeDelta;
+
+/// Параметры расчёта статистики.
+#[derive(Clone, Copy, Debug)]
+pub struct StatsOptions {
+    pub range: DateRange,
+    /// Паузы длиннее этой (например, ночной перерыв) не считаются ответом.
+    pub max_reply_gap: TimeDelta,
+    /// Пауза, после которой начинается новый разговор.
+    pub conversation_gap: TimeDelta,
+    /// Сколько позиций оставлять в топах.
+    pub top_n: usize,
+}
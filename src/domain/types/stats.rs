@@ -1,20 +1,63 @@
-use crate::domain::types::chat::{Message, MessageText};
+use crate::domain::types::chat::{Message, MessageText, Poll};
 use chrono::{DateTime, NaiveDate, Utc};
 use serde::Serialize;
+use std::collections::BTreeMap;
+
+#[derive(Serialize)]
+#[serde(untagged)]
+pub enum StatsReport {
+    Chat(Box<AllStats>),
+    Account(ExportStats),
+}
+
+#[derive(Serialize)]
+pub struct ExportStats {
+    pub owner_id: String,
+    pub chats: Vec<AllStats>,
+}
 
 #[derive(Serialize)]
 pub struct AllStats {
-    pub year: i32,
+    pub chat_id: i64,
+    pub chat_name: Option<String>,
+    pub chat_type: String,
+    /// Год, если статистика считается ровно за календарный год.
+    pub year: Option<i32>,
+    pub period: Period,
     pub source_dir: String,
+    pub owner_id: String,
     pub chat_stats: ChatStats,
+    pub timeline: TimelineStats,
     pub occurrences: MessagesStats,
     pub longest_conversation: MessagesStats,
+    pub conversations: ConversationStats,
     pub calls_stats: CallsStats,
     pub most_used_sticker: MostUsedSticker,
     pub emoji_stats: EmojiStats,
     pub word_stats: WordStats,
     pub avg_messages_per_day: f64,
     pub streak: Streak,
+    pub participants: BTreeMap<String, ParticipantStats>,
+    pub activity: ActivityStats,
+    pub response_times: ResponseTimeStats,
+    pub media: MediaStats,
+    pub stickers: StickersStats,
+    pub reactions: ReactionStats,
+    pub entities: EntityStats,
+    pub forwards: ForwardStats,
+    pub replies: ReplyGraphStats,
+    pub edits: EditStats,
+    pub polls: PollStats,
+}
+
+/// Фактический период статистики, `to` не включительно.
+#[derive(Serialize)]
+pub struct Period {
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+    pub days: i64,
+    /// IANA-часовой пояс, в котором считаются дни и часы.
+    pub timezone: String,
 }
 
 #[derive(Serialize)]
@@ -23,6 +66,32 @@ pub struct ChatStats {
     pub additional_messages_stats: AdditionalMessagesStats,
 }
 
+/// Временные ряды за период, в часовом поясе периода. Дни без сообщений
+/// между первым и последним сообщением тоже попадают в ряды.
+#[derive(Serialize)]
+pub struct TimelineStats {
+    pub daily: Vec<TimelinePoint>,
+    pub weekly: Vec<TimelinePoint>,
+    pub monthly: Vec<TimelinePoint>,
+    pub busiest_day: Option<TimelinePoint>,
+    pub busiest_week: Option<TimelinePoint>,
+    pub busiest_month: Option<TimelinePoint>,
+}
+
+#[derive(Clone, Serialize)]
+pub struct TimelinePoint {
+    /// Начало отрезка: день, понедельник недели или первое число месяца.
+    pub date: NaiveDate,
+    pub messages_count: usize,
+    pub owner_messages_count: usize,
+    pub member_messages_count: usize,
+    pub characters_count: usize,
+    pub calls_duration_sec: u32,
+    pub calls_duration_min: u32,
+    /// Число сообщений по `from_id` отправителя.
+    pub participants: BTreeMap<String, usize>,
+}
+
 #[derive(Serialize)]
 pub struct MessagesStats {
     pub first_message: Option<MinimalMessage>,
@@ -39,11 +108,83 @@ pub struct AdditionalMessagesStats {
     pub member_characters_count: usize,
 }
 
+/// Разговоры: цепочки сообщений с паузами короче `gap_sec`.
+#[derive(Serialize)]
+pub struct ConversationStats {
+    pub gap_sec: i64,
+    pub conversations_count: usize,
+    pub avg_messages_count: f64,
+    pub avg_duration_sec: f64,
+    pub longest_by_messages: Vec<Conversation>,
+    pub longest_by_duration: Vec<Conversation>,
+    pub participants: BTreeMap<String, ConversationInitiative>,
+}
+
+/// Кто начинает и заканчивает разговоры. Двойное сообщение — разговор, начатый
+/// тем же участником, на чьём сообщении закончился предыдущий, без ответа.
+#[derive(Default, Serialize)]
+pub struct ConversationInitiative {
+    pub started_count: usize,
+    pub started_share: f64,
+    pub ended_count: usize,
+    pub ended_share: f64,
+    pub double_texts_count: usize,
+    /// Доля двойных сообщений среди начатых участником разговоров.
+    pub double_texts_share: f64,
+}
+
+#[derive(Clone, Serialize)]
+pub struct Conversation {
+    pub start: DateTime<Utc>,
+    pub end: DateTime<Utc>,
+    pub duration_sec: i64,
+    pub messages_count: usize,
+    /// Число сообщений по `from_id` отправителя.
+    pub participants: BTreeMap<String, usize>,
+    pub started_by: Option<String>,
+    pub ended_by: Option<String>,
+    pub first_message: MinimalMessage,
+    pub last_message: MinimalMessage,
+}
+
+/// Звонки по сервисным сообщениям `phone_call`. Часы и месяцы считаются
+/// в часовом поясе периода.
 #[derive(Serialize)]
 pub struct CallsStats {
+    pub calls_count: usize,
+    pub answered_calls_count: usize,
     pub total_calls_durations_sec: u32,
     pub total_calls_durations_min: u32,
-    pub longest_call_durations_min: Option<MinimalMessage>,
+    /// Средняя длительность состоявшегося звонка.
+    pub avg_call_duration_sec: f64,
+    pub longest_call: Option<MinimalMessage>,
+    pub longest_call_duration_min: u32,
+    /// Число звонков по `discard_reason`: `hangup`, `missed`, `busy`, ...
+    pub by_outcome: BTreeMap<String, usize>,
+    pub by_hour: [usize; 24],
+    pub by_month: Vec<MonthlyCalls>,
+    pub participants: BTreeMap<String, ParticipantCalls>,
+}
+
+#[derive(Serialize)]
+pub struct MonthlyCalls {
+    pub month: NaiveDate,
+    pub calls_count: usize,
+    pub duration_sec: u32,
+    pub duration_min: u32,
+}
+
+/// Звонки участника. Входящие известны только в личном чате, где все звонки
+/// собеседника адресованы участнику; в группах они не заполняются.
+#[derive(Default, Serialize)]
+pub struct ParticipantCalls {
+    pub outgoing_count: usize,
+    pub incoming_count: Option<usize>,
+    /// Исходящие звонки, которые не приняли.
+    pub unanswered_outgoing_count: usize,
+    /// Входящие звонки, пропущенные участником.
+    pub missed_incoming_count: Option<usize>,
+    pub outgoing_duration_sec: u32,
 }
 
 #[derive(Serialize)]
@@ -58,6 +199,18 @@ pub struct MostUsedSticker {
 pub struct EmojiStats {
     pub top_emoji: Option<String>,
     pub top_emoji_count: i32,
+    pub emoji_count: usize,
+    pub top_emojis: Vec<EmojiCount>,
+    /// Среднее число эмодзи на сообщение.
+    pub emoji_per_message: f64,
+    /// Доля сообщений хотя бы с одним эмодзи.
+    pub messages_with_emoji_share: f64,
+}
+
+#[derive(Serialize)]
+pub struct EmojiCount {
+    pub emoji: String,
+    pub count: i32,
 }
 
 #[derive(Serialize)]
@@ -65,13 +218,306 @@ pub struct WordStats {
     pub top_words: Vec<WordCount>,
 }
 
+#[derive(Serialize)]
+pub struct ParticipantStats {
+    pub name: Option<String>,
+    pub messages_count: usize,
+    pub characters_count: usize,
+    pub most_used_sticker_count: i32,
+    pub most_used_sticker: Option<MinimalMessage>,
+    #[serde(flatten)]
+    pub emoji: EmojiStats,
+    pub top_words: Vec<WordCount>,
+}
+
+/// Активность по дням недели и часам, в часовом поясе периода.
+#[derive(Serialize)]
+pub struct Activity {
+    /// Число сообщений: `heatmap[день недели][час]`, неделя начинается с понедельника.
+    pub heatmap: [[u32; 24]; 7],
+    pub busiest_hour: Option<u32>,
+    pub most_active_weekday: Option<String>,
+    /// Доля сообщений, отправленных с 00:00 до 05:59.
+    pub night_owl_share: f64,
+}
+
+#[derive(Serialize)]
+pub struct ActivityStats {
+    #[serde(flatten)]
+    pub total: Activity,
+    pub participants: BTreeMap<String, Activity>,
+}
+
+/// Скорость ответов: время от последнего сообщения собеседника до следующего
+/// сообщения участника. Паузы длиннее `max_reply_gap_sec` не учитываются.
+#[derive(Serialize)]
+pub struct ResponseTimeStats {
+    pub max_reply_gap_sec: i64,
+    pub participants: BTreeMap<String, ResponseTimes>,
+}
+
+#[derive(Serialize)]
+pub struct ResponseTimes {
+    pub replies_count: usize,
+    pub median_sec: Option<i64>,
+    pub p90_sec: Option<i64>,
+    pub fastest: Option<Reply>,
+    pub slowest: Option<Reply>,
+    /// Ответы по часу отправки, в часовом поясе периода.
+    pub by_hour: Vec<HourlyResponseTimes>,
+}
+
+#[derive(Serialize)]
+pub struct Reply {
+    pub seconds: i64,
+    pub message: MinimalMessage,
+}
+
+#[derive(Serialize)]
+pub struct HourlyResponseTimes {
+    pub hour: u32,
+    pub replies_count: usize,
+    pub median_sec: Option<i64>,
+}
+
+/// Медиа по типам: `photo`, `video_file`, `voice_message`, `video_message`,
+/// `audio_file`, `animation`, `sticker`, `document` и прочие из экспорта.
+#[derive(Serialize)]
+pub struct MediaStats {
+    pub media_count: usize,
+    pub by_type: BTreeMap<String, usize>,
+    /// Число медиа каждого типа по `from_id` отправителя.
+    pub participants: BTreeMap<String, BTreeMap<String, usize>>,
+    pub voice_messages_duration_sec: u32,
+    pub voice_messages_duration_min: u32,
+    pub video_messages_duration_sec: u32,
+    pub video_messages_duration_min: u32,
+    pub longest_voice_message: Option<MinimalMessage>,
+    pub longest_voice_message_duration_sec: u32,
+    /// Документы по `mime_type`.
+    pub documents_by_mime_type: BTreeMap<String, usize>,
+}
+
+/// Топ стикеров. Набор стикеров в экспорт не попадает, поэтому стикеры
+/// группируются только по файлу и по эмодзи.
+#[derive(Serialize)]
+pub struct StickerStats {
+    pub stickers_count: usize,
+    pub unique_stickers_count: usize,
+    pub top_stickers: Vec<StickerCount>,
+    pub top_sticker_emoji: Vec<StickerEmojiCount>,
+}
+
+#[derive(Serialize)]
+pub struct StickersStats {
+    #[serde(flatten)]
+    pub total: StickerStats,
+    pub participants: BTreeMap<String, StickerStats>,
+}
+
+#[derive(Serialize)]
+pub struct StickerCount {
+    pub count: usize,
+    /// Первое сообщение с этим стикером.
+    pub message: MinimalMessage,
+}
+
+#[derive(Serialize)]
+pub struct StickerEmojiCount {
+    pub emoji: String,
+    pub count: usize,
+}
+
+/// Реакции на сообщения. Кто поставил реакцию, экспорт сообщает только для
+/// последних реакций (`recent`), поэтому разбивка по участникам может быть
+/// неполной.
+#[derive(Serialize)]
+pub struct ReactionStats {
+    pub reactions_count: usize,
+    pub reacted_messages_count: usize,
+    pub top_reactions: Vec<ReactionCount>,
+    pub most_reacted_message: Option<MinimalMessage>,
+    pub most_reacted_message_reactions_count: usize,
+    pub participants: BTreeMap<String, ParticipantReactions>,
+}
+
+#[derive(Serialize)]
+pub struct ReactionCount {
+    pub reaction: String,
+    pub count: usize,
+}
+
+#[derive(Default, Serialize)]
+pub struct ParticipantReactions {
+    pub given_count: usize,
+    pub received_count: usize,
+    pub most_used_reaction: Option<String>,
+    pub most_used_reaction_count: usize,
+    /// Число реакций на сообщения каждого участника, по `from_id` автора.
+    pub reacted_to: BTreeMap<String, usize>,
+    pub most_reacted_to: Option<String>,
+}
+
+/// Сущности текста (`link`, `text_link`, `mention`, `hashtag`, `bot_command`,
+/// `email`, `code`, ...). Обычный текст (`plain`) не учитывается.
+#[derive(Serialize)]
+pub struct EntityStats {
+    pub entities_count: usize,
+    pub by_type: BTreeMap<String, usize>,
+    pub top_domains: Vec<EntityCount>,
+    pub top_links: Vec<EntityCount>,
+    /// `@username` или `user<id>` для упоминаний пользователей без username.
+    pub top_mentions: Vec<EntityCount>,
+    pub top_hashtags: Vec<EntityCount>,
+    /// Число сущностей каждого типа по `from_id` отправителя.
+    pub participants: BTreeMap<String, BTreeMap<String, usize>>,
+}
+
+#[derive(Serialize)]
+pub struct EntityCount {
+    pub value: String,
+    pub count: usize,
+}
+
+/// Пересланные сообщения (`forwarded_from`, а в «Избранном» `saved_from`)
+/// и сообщения через инлайн-ботов (`via_bot`).
+#[derive(Serialize)]
+pub struct ForwardStats {
+    pub messages_count: usize,
+    pub forwarded_count: usize,
+    pub forwarded_share: f64,
+    pub via_bot_count: usize,
+    pub top_forwarded_from: Vec<SourceCount>,
+    pub top_via_bots: Vec<SourceCount>,
+    pub participants: BTreeMap<String, ForwardShare>,
+    /// По месяцам в часовом поясе периода.
+    pub monthly: Vec<MonthlyForwards>,
+}
+
+#[derive(Default, Serialize)]
+pub struct ForwardShare {
+    pub messages_count: usize,
+    pub forwarded_count: usize,
+    pub forwarded_share: f64,
+    pub via_bot_count: usize,
+}
+
+#[derive(Serialize)]
+pub struct MonthlyForwards {
+    pub month: NaiveDate,
+    #[serde(flatten)]
+    pub share: ForwardShare,
+}
+
+#[derive(Serialize)]
+pub struct SourceCount {
+    pub name: String,
+    pub count: usize,
+}
+
+/// Ответы по `reply_to_message_id`: кто кому отвечает и самые длинные ветки.
+#[derive(Serialize)]
+pub struct ReplyGraphStats {
+    pub replies_count: usize,
+    pub most_replied_message_id: Option<i64>,
+    pub most_replied_message: Option<MinimalMessage>,
+    pub most_replied_message_replies_count: usize,
+    /// Самая длинная цепочка ответов, от первого сообщения к последнему.
+    pub deepest_thread_ids: Vec<i64>,
+    pub deepest_thread: Vec<MinimalMessage>,
+    pub deepest_thread_depth: usize,
+    pub participants: BTreeMap<String, ParticipantReplies>,
+}
+
+impl ReplyGraphStats {
+    /// Есть ли сообщения, которые ещё нужно подставить через [`Self::resolve`].
+    pub fn is_resolved(&self) -> bool {
+        self.most_replied_message.is_some() == self.most_replied_message_id.is_some()
+            && self.deepest_thread.len() == self.deepest_thread_ids.len()
+    }
+
+    /// Подставляет сообщение, если оно входит в результат. Сообщения нужно
+    /// передавать в хронологическом порядке.
+    pub fn resolve(&mut self, message: &Message) {
+        if self.most_replied_message_id == Some(message.id) {
+            self.most_replied_message = Some(message.into());
+        }
+        if self.deepest_thread_ids.get(self.deepest_thread.len()) == Some(&message.id) {
+            self.deepest_thread.push(message.into());
+        }
+    }
+}
+
+#[derive(Default, Serialize)]
+pub struct ParticipantReplies {
+    pub messages_count: usize,
+    pub replies_count: usize,
+    /// Доля сообщений участника, отправленных ответом.
+    pub reply_share: f64,
+    /// Число ответов на сообщения каждого участника, по `from_id` автора.
+    pub replied_to: BTreeMap<String, usize>,
+    pub most_replied_to: Option<String>,
+}
+
+/// Правки сообщений: задержка — время от отправки до последней правки.
+#[derive(Serialize)]
+pub struct EditStats {
+    pub edited_count: usize,
+    pub edited_share: f64,
+    pub median_latency_sec: Option<i64>,
+    pub p90_latency_sec: Option<i64>,
+    pub latency_distribution: Vec<EditLatencyBucket>,
+    /// Сообщение, исправленное позже всех после отправки.
+    pub longest_edit: Option<EditedMessage>,
+    pub participants: BTreeMap<String, ParticipantEdits>,
+}
+
+/// Правки с задержкой меньше `max_sec` и не меньше границы предыдущей корзины;
+/// у последней корзины граница не задана.
+#[derive(Serialize)]
+pub struct EditLatencyBucket {
+    pub max_sec: Option<i64>,
+    pub edited_count: usize,
+}
+
+#[derive(Serialize)]
+pub struct EditedMessage {
+    pub latency_sec: i64,
+    pub message: MinimalMessage,
+}
+
+#[derive(Serialize)]
+pub struct ParticipantEdits {
+    pub messages_count: usize,
+    pub edited_count: usize,
+    pub edited_share: f64,
+    pub median_latency_sec: Option<i64>,
+}
+
+#[derive(Serialize)]
+pub struct PollStats {
+    pub polls_count: usize,
+    pub closed_count: usize,
+    pub open_count: usize,
+    pub total_voters: usize,
+    pub most_voted_poll: Option<MinimalMessage>,
+    pub most_voted_poll_voters_count: i32,
+    /// Число созданных опросов по `from_id` автора.
+    pub participants: BTreeMap<String, usize>,
+    /// Опросы, в которых голосовал владелец экспорта.
+    pub owner_voted_count: usize,
+    /// Из них — с голосом за ответ, набравший больше всех голосов.
+    pub owner_voted_for_winner_count: usize,
+    pub owner_voted_for_winner_share: f64,
+}
+
 #[derive(Serialize)]
 pub struct WordCount {
     pub word: String,
     pub count: i32,
 }
 
-#[derive(Serialize)]
+#[derive(Clone, Serialize)]
 pub struct MinimalMessage {
     pub id: i64,
     pub from: Option<String>,
@@ -82,7 +528,11 @@ pub struct MinimalMessage {
     pub discard_reason: Option<String>,
     pub file: Option<String>,
     pub file_name: Option<String>,
+    pub photo: Option<String>,
+    pub thumbnail: Option<String>,
     pub media_type: Option<String>,
+    pub sticker_emoji: Option<String>,
+    pub poll: Option<Poll>,
 }
 
 #[derive(Serialize)]
@@ -92,6 +542,27 @@ pub struct Streak {
     pub end: NaiveDate,
 }
 
+impl From<&Message> for MinimalMessage {
+    fn from(value: &Message) -> Self {
+        Self {
+            id: value.id,
+            from: value.from.clone(),
+            r#type: value.r#type.clone(),
+            text: value.text.clone(),
+            date: value.date,
+            duration_seconds: value.duration_seconds,
+            discard_reason: value.discard_reason.clone(),
+            file: value.file.clone(),
+            file_name: value.file_name.clone(),
+            photo: value.photo.clone(),
+            thumbnail: value.thumbnail.clone(),
+            media_type: value.media_type.clone(),
+            sticker_emoji: value.sticker_emoji.clone(),
+            poll: value.poll.clone(),
+        }
+    }
+}
+
 impl From<Message> for MinimalMessage {
     fn from(value: Message) -> Self {
         Self {
@@ -104,7 +575,11 @@ impl From<Message> for MinimalMessage {
             discard_reason: value.discard_reason,
             file: value.file,
             file_name: value.file_name,
+            photo: value.photo,
+            thumbnail: value.thumbnail,
             media_type: value.media_type,
+            sticker_emoji: value.sticker_emoji,
+            poll: value.poll,
         }
     }
 }
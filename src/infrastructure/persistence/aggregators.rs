@@ -0,0 +1,2631 @@
+use crate::domain::types::chat::{ChatOwner, Message};
+use crate::domain::types::options::StatsOptions;
+use crate::domain::types::stats::{
+    Activity, ActivityStats, AdditionalMessagesStats, AllStats, CallsStats, ChatStats,
+    Conversation, ConversationInitiative, ConversationStats, EditLatencyBucket, EditStats,
+    EditedMessage, EmojiCount, EmojiStats, EntityCount, EntityStats, ForwardShare, ForwardStats,
+    HourlyResponseTimes, MediaStats, MessagesStats, MinimalMessage, MonthlyCalls, MonthlyForwards,
+    MostUsedSticker, ParticipantCalls, ParticipantEdits, ParticipantReactions, ParticipantReplies,
+    ParticipantStats, PollStats, ReactionCount, ReactionStats, Reply, ReplyGraphStats,
+    ResponseTimeStats, ResponseTimes, SourceCount, StickerCount, StickerEmojiCount, StickerStats,
+    StickersStats, Streak, TimelinePoint, TimelineStats, WordCount, WordStats,
+};
+use crate::infrastructure::repositories::aggregator::Aggregator;
+use crate::ingest::data_preparer::{DataPreparer, OCCURRENCES_RE};
+use crate::ingest::message_stream::ChatHeader;
+use chrono::{DateTime, Datelike, Duration, NaiveDate, TimeDelta, Timelike, Utc};
+use chrono_tz::Tz;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+
+/// Пропускает во внутренний сборщик только сообщения, удовлетворяющие фильтру.
+pub struct Filtered<A> {
+    inner: A,
+    filter: fn(&Message) -> bool,
+}
+
+impl<A> Filtered<A> {
+    pub fn new(inner: A, filter: fn(&Message) -> bool) -> Self {
+        Self { inner, filter }
+    }
+}
+
+impl<A: Aggregator> Aggregator for Filtered<A> {
+    type Output = A::Output;
+
+    fn observe(&mut self, message: &Message) {
+        if (self.filter)(message) {
+            self.inner.observe(message);
+        }
+    }
+
+    fn finish(self) -> Self::Output {
+        self.inner.finish()
+    }
+}
+
+pub struct MessagesStatsAggregator {
+    owner: ChatOwner,
+    first_message: Option<MinimalMessage>,
+    last_message: Option<MinimalMessage>,
+    total_messages_count: usize,
+    owner_messages_count: usize,
+    member_messages_count: usize,
+}
+
+impl MessagesStatsAggregator {
+    pub fn new(owner: ChatOwner) -> Self {
+        Self {
+            owner,
+            first_message: None,
+            last_message: None,
+            total_messages_count: 0,
+            owner_messages_count: 0,
+            member_messages_count: 0,
+        }
+    }
+}
+
+impl Aggregator for MessagesStatsAggregator {
+    type Output = MessagesStats;
+
+    fn observe(&mut self, message: &Message) {
+        self.total_messages_count += 1;
+        if self.owner.is_owner(message) {
+            self.owner_messages_count += 1;
+        } else if self.owner.is_member(message) {
+            self.member_messages_count += 1;
+        }
+
+        if self
+            .first_message
+            .as_ref()
+            .is_none_or(|first| message.date < first.date)
+        {
+            self.first_message = Some(message.into());
+        }
+        if self
+            .last_message
+            .as_ref()
+            .is_none_or(|last| message.date >= last.date)
+        {
+            self.last_message = Some(message.into());
+        }
+    }
+
+    fn finish(self) -> Self::Output {
+        MessagesStats {
+            first_message: self.first_message,
+            last_message: self.last_message,
+            total_messages_count: self.total_messages_count,
+            owner_messages_count: self.owner_messages_count,
+            member_messages_count: self.member_messages_count,
+        }
+    }
+}
+
+pub struct AdditionalMessagesStatsAggregator {
+    owner: ChatOwner,
+    stats: AdditionalMessagesStats,
+}
+
+impl AdditionalMessagesStatsAggregator {
+    pub fn new(owner: ChatOwner) -> Self {
+        Self {
+            owner,
+            stats: AdditionalMessagesStats {
+                total_characters_count: 0,
+                owner_characters_count: 0,
+                member_characters_count: 0,
+            },
+        }
+    }
+}
+
+impl Aggregator for AdditionalMessagesStatsAggregator {
+    type Output = AdditionalMessagesStats;
+
+    fn observe(&mut self, message: &Message) {
+        let characters = DataPreparer::message_characters(message);
+
+        self.stats.total_characters_count += characters;
+        if self.owner.is_owner(message) {
+            self.stats.owner_characters_count += characters;
+        } else if self.owner.is_member(message) {
+            self.stats.member_characters_count += characters;
+        }
+    }
+
+    fn finish(self) -> Self::Output {
+        self.stats
+    }
+}
+
+/// Все разговоры чата: топы по числу сообщений и длительности, средние значения.
+pub struct ConversationsAggregator {
+    gap: TimeDelta,
+    top_n: usize,
+    current: Option<Conversation>,
+    conversations_count: usize,
+    messages_count: usize,
+    duration_sec: i64,
+    longest_by_messages: Vec<Conversation>,
+    longest_by_duration: Vec<Conversation>,
+    last_ended_by: Option<String>,
+    participants: BTreeMap<String, ConversationInitiative>,
+}
+
+impl ConversationsAggregator {
+    pub fn new(gap: TimeDelta, top_n: usize) -> Self {
+        Self {
+            gap,
+            top_n,
+            current: None,
+            conversations_count: 0,
+            messages_count: 0,
+            duration_sec: 0,
+            longest_by_messages: vec![],
+            longest_by_duration: vec![],
+            last_ended_by: None,
+            participants: BTreeMap::new(),
+        }
+    }
+
+    /// Вставляет разговор в топ, при равенстве выше остаётся более ранний.
+    fn push_top(
+        top: &mut Vec<Conversation>,
+        top_n: usize,
+        conversation: &Conversation,
+        key: fn(&Conversation) -> i64,
+    ) {
+        let position = top
+            .iter()
+            .position(|item| key(conversation) > key(item))
+            .unwrap_or(top.len());
+        if position < top_n {
+            top.insert(position, conversation.clone());
+            top.truncate(top_n);
+        }
+    }
+
+    fn close_current(&mut self) {
+        let Some(conversation) = self.current.take() else {
+            return;
+        };
+
+        if let Some(started_by) = &conversation.started_by {
+            let participant = self.participants.entry(started_by.clone()).or_default();
+            participant.started_count += 1;
+            if self.last_ended_by.as_ref() == Some(started_by) {
+                participant.double_texts_count += 1;
+            }
+        }
+        if let Some(ended_by) = &conversation.ended_by {
+            self.participants
+                .entry(ended_by.clone())
+                .or_default()
+                .ended_count += 1;
+        }
+        self.last_ended_by = conversation.ended_by.clone();
+
+        self.conversations_count += 1;
+        self.messages_count += conversation.messages_count;
+        self.duration_sec += conversation.duration_sec;
+        Self::push_top(
+            &mut self.longest_by_messages,
+            self.top_n,
+            &conversation,
+            |conversation| conversation.messages_count as i64,
+        );
+        Self::push_top(
+            &mut self.longest_by_duration,
+            self.top_n,
+            &conversation,
+            |conversation| conversation.duration_sec,
+        );
+    }
+}
+
+impl Aggregator for ConversationsAggregator {
+    type Output = ConversationStats;
+
+    fn observe(&mut self, message: &Message) {
+        if self
+            .current
+            .as_ref()
+            .is_some_and(|current| message.date - current.end >= self.gap)
+        {
+            self.close_current();
+        }
+
+        let sender = message.from_id.clone().or_else(|| message.actor_id.clone());
+        let current = self.current.get_or_insert_with(|| Conversation {
+            start: message.date,
+            end: message.date,
+            duration_sec: 0,
+            messages_count: 0,
+            participants: BTreeMap::new(),
+            started_by: sender.clone(),
+            ended_by: None,
+            first_message: message.into(),
+            last_message: message.into(),
+        });
+
+        current.end = message.date;
+        current.last_message = message.into();
+        current.duration_sec = (current.end - current.start).num_seconds();
+        current.messages_count += 1;
+        if let Some(sender) = &sender {
+            *current.participants.entry(sender.clone()).or_default() += 1;
+        }
+        current.ended_by = sender;
+    }
+
+    fn finish(mut self) -> Self::Output {
+        self.close_current();
+
+        let share = |part: usize, total: usize| match total {
+            0 => 0.0,
+            total => part as f64 / total as f64,
+        };
+        let mut participants = self.participants;
+        for participant in participants.values_mut() {
+            participant.started_share = share(participant.started_count, self.conversations_count);
+            participant.ended_share = share(participant.ended_count, self.conversations_count);
+            participant.double_texts_share =
+                share(participant.double_texts_count, participant.started_count);
+        }
+        let average = |total: f64| match self.conversations_count {
+            0 => 0.0,
+            count => total / count as f64,
+        };
+
+        ConversationStats {
+            gap_sec: self.gap.num_seconds(),
+            conversations_count: self.conversations_count,
+            avg_messages_count: average(self.messages_count as f64),
+            avg_duration_sec: average(self.duration_sec as f64),
+            longest_by_messages: self.longest_by_messages,
+            longest_by_duration: self.longest_by_duration,
+            participants,
+        }
+    }
+}
+
+/// Звонки чата. Смотрит все сообщения, чтобы знать участников, которым
+/// адресованы звонки. Входящие считаются только для личного чата.
+pub struct CallsAggregator {
+    timezone: Tz,
+    personal: bool,
+    calls_count: usize,
+    answered_calls_count: usize,
+    total_duration: u32,
+    longest_duration: i32,
+    longest_call: Option<MinimalMessage>,
+    by_outcome: BTreeMap<String, usize>,
+    by_hour: [usize; 24],
+    by_month: BTreeMap<NaiveDate, (usize, u32)>,
+    participants: BTreeMap<String, ParticipantCalls>,
+    missed_outgoing: HashMap<String, usize>,
+}
+
+impl CallsAggregator {
+    pub fn new(timezone: Tz, personal: bool) -> Self {
+        Self {
+            timezone,
+            personal,
+            calls_count: 0,
+            answered_calls_count: 0,
+            total_duration: 0,
+            longest_duration: 0,
+            longest_call: None,
+            by_outcome: BTreeMap::new(),
+            by_hour: [0; 24],
+            by_month: BTreeMap::new(),
+            participants: BTreeMap::new(),
+            missed_outgoing: HashMap::new(),
+        }
+    }
+}
+
+impl Aggregator for CallsAggregator {
+    type Output = CallsStats;
+
+    fn observe(&mut self, message: &Message) {
+        const MISSED: &str = "missed";
+        const BUSY: &str = "busy";
+
+        let sender = message.from_id.as_ref().or(message.actor_id.as_ref());
+        if let Some(sender) = sender {
+            self.participants.entry(sender.clone()).or_default();
+        }
+        if message.action.as_deref() != Some("phone_call") {
+            return;
+        }
+
+        let duration = message.duration_seconds.unwrap_or(0).max(0);
+        let outcome = message.discard_reason.as_deref().unwrap_or("unknown");
+        let missed = outcome == MISSED;
+        let answered = duration > 0 && !matches!(outcome, MISSED | BUSY);
+        let date = message.date.with_timezone(&self.timezone);
+
+        self.calls_count += 1;
+        if answered {
+            self.answered_calls_count += 1;
+        }
+        self.total_duration += duration as u32;
+        if duration > self.longest_duration {
+            self.longest_duration = duration;
+            self.longest_call = Some(message.into());
+        }
+        *self.by_outcome.entry(outcome.to_string()).or_default() += 1;
+        self.by_hour[date.hour() as usize] += 1;
+        let month = self
+            .by_month
+            .entry(date.date_naive().with_day(1).unwrap_or(date.date_naive()))
+            .or_default();
+        month.0 += 1;
+        month.1 += duration as u32;
+
+        if let Some(sender) = sender {
+            let caller = self.participants.entry(sender.clone()).or_default();
+            caller.outgoing_count += 1;
+            caller.outgoing_duration_sec += duration as u32;
+            if !answered {
+                caller.unanswered_outgoing_count += 1;
+            }
+            if missed {
+                *self.missed_outgoing.entry(sender.clone()).or_default() += 1;
+            }
+        }
+    }
+
+    fn finish(self) -> Self::Output {
+        let mut participants = self.participants;
+        if self.personal {
+            let outgoing: usize = participants.values().map(|p| p.outgoing_count).sum();
+            let missed: usize = self.missed_outgoing.values().sum();
+            for (id, participant) in participants.iter_mut() {
+                participant.incoming_count = Some(outgoing - participant.outgoing_count);
+                participant.missed_incoming_count =
+                    Some(missed - self.missed_outgoing.get(id).copied().unwrap_or(0));
+            }
+        }
+
+        CallsStats {
+            calls_count: self.calls_count,
+            answered_calls_count: self.answered_calls_count,
+            total_calls_durations_sec: self.total_duration,
+            total_calls_durations_min: self.total_duration / 60,
+            avg_call_duration_sec: match self.answered_calls_count {
+                0 => 0.0,
+                count => self.total_duration as f64 / count as f64,
+            },
+            longest_call: self.longest_call,
+            longest_call_duration_min: self.longest_duration as u32 / 60,
+            by_outcome: self.by_outcome,
+            by_hour: self.by_hour,
+            by_month: self
+                .by_month
+                .into_iter()
+                .map(|(month, (calls_count, duration_sec))| MonthlyCalls {
+                    month,
+                    calls_count,
+                    duration_sec,
+                    duration_min: duration_sec / 60,
+                })
+                .collect(),
+            participants,
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct MediaAggregator {
+    media_count: usize,
+    by_type: BTreeMap<String, usize>,
+    participants: BTreeMap<String, BTreeMap<String, usize>>,
+    voice_duration: u32,
+    video_duration: u32,
+    longest_voice_duration: u32,
+    longest_voice_message: Option<MinimalMessage>,
+    documents_by_mime_type: BTreeMap<String, usize>,
+}
+
+impl Aggregator for MediaAggregator {
+    type Output = MediaStats;
+
+    fn observe(&mut self, message: &Message) {
+        if message.r#type != "message" {
+            return;
+        }
+        let Some(media_type) = DataPreparer::media_type(message) else {
+            return;
+        };
+        let duration = message.duration_seconds.unwrap_or(0).max(0) as u32;
+
+        self.media_count += 1;
+        *self.by_type.entry(media_type.to_string()).or_default() += 1;
+        if let Some(id) = &message.from_id {
+            *self
+                .participants
+                .entry(id.clone())
+                .or_default()
+                .entry(media_type.to_string())
+                .or_default() += 1;
+        }
+
+        match media_type {
+            "voice_message" => {
+                self.voice_duration += duration;
+                if duration > self.longest_voice_duration {
+                    self.longest_voice_duration = duration;
+                    self.longest_voice_message = Some(message.into());
+                }
+            }
+            "video_message" => self.video_duration += duration,
+            "document" => {
+                let mime_type = message.mime_type.as_deref().unwrap_or("unknown");
+                *self
+                    .documents_by_mime_type
+                    .entry(mime_type.to_string())
+                    .or_default() += 1;
+            }
+            _ => {}
+        }
+    }
+
+    fn finish(self) -> Self::Output {
+        MediaStats {
+            media_count: self.media_count,
+            by_type: self.by_type,
+            participants: self.participants,
+            voice_messages_duration_sec: self.voice_duration,
+            voice_messages_duration_min: self.voice_duration / 60,
+            video_messages_duration_sec: self.video_duration,
+            video_messages_duration_min: self.video_duration / 60,
+            longest_voice_message: self.longest_voice_message,
+            longest_voice_message_duration_sec: self.longest_voice_duration,
+            documents_by_mime_type: self.documents_by_mime_type,
+        }
+    }
+}
+
+/// Реакции: самые частые, самое отреагированное сообщение и кто кому ставит
+/// реакции.
+pub struct ReactionAggregator {
+    top_n: usize,
+    reactions_count: usize,
+    reacted_messages_count: usize,
+    counts: HashMap<String, usize>,
+    most_reacted_count: usize,
+    most_reacted_message: Option<MinimalMessage>,
+    participants: BTreeMap<String, ParticipantReactions>,
+    given: HashMap<String, HashMap<String, usize>>,
+}
+
+impl ReactionAggregator {
+    pub fn new(top_n: usize) -> Self {
+        Self {
+            top_n,
+            reactions_count: 0,
+            reacted_messages_count: 0,
+            counts: HashMap::new(),
+            most_reacted_count: 0,
+            most_reacted_message: None,
+            participants: BTreeMap::new(),
+            given: HashMap::new(),
+        }
+    }
+
+    /// Ключ с наибольшим значением, при равенстве — меньший.
+    fn max_entry<'a>(
+        counts: impl IntoIterator<Item = (&'a String, &'a usize)>,
+    ) -> Option<(String, usize)> {
+        counts
+            .into_iter()
+            .max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0)))
+            .map(|(key, count)| (key.clone(), *count))
+    }
+}
+
+impl Aggregator for ReactionAggregator {
+    type Output = ReactionStats;
+
+    fn observe(&mut self, message: &Message) {
+        let Some(reactions) = message.reactions.as_ref().filter(|r| !r.is_empty()) else {
+            return;
+        };
+
+        let count: usize = reactions.iter().map(|r| r.count.max(0) as usize).sum();
+        self.reactions_count += count;
+        self.reacted_messages_count += 1;
+        if count > self.most_reacted_count {
+            self.most_reacted_count = count;
+            self.most_reacted_message = Some(message.into());
+        }
+        if let Some(author) = &message.from_id {
+            self.participants
+                .entry(author.clone())
+                .or_default()
+                .received_count += count;
+        }
+
+        for reaction in reactions {
+            let Some(key) = DataPreparer::reaction_key(reaction) else {
+                continue;
+            };
+            *self.counts.entry(key.clone()).or_default() += reaction.count.max(0) as usize;
+
+            for sender in &reaction.recent {
+                let Some(sender) = &sender.from_id else {
+                    continue;
+                };
+                let participant = self.participants.entry(sender.clone()).or_default();
+                participant.given_count += 1;
+                if let Some(author) = &message.from_id {
+                    *participant.reacted_to.entry(author.clone()).or_default() += 1;
+                }
+                *self
+                    .given
+                    .entry(sender.clone())
+                    .or_default()
+                    .entry(key.clone())
+                    .or_default() += 1;
+            }
+        }
+    }
+
+    fn finish(self) -> Self::Output {
+        let mut participants = self.participants;
+        for (id, participant) in participants.iter_mut() {
+            if let Some((reaction, count)) = self.given.get(id).and_then(Self::max_entry) {
+                participant.most_used_reaction = Some(reaction);
+                participant.most_used_reaction_count = count;
+            }
+            participant.most_reacted_to =
+                Self::max_entry(&participant.reacted_to).map(|(id, _)| id);
+        }
+
+        let mut top_reactions: Vec<ReactionCount> = self
+            .counts
+            .into_iter()
+            .map(|(reaction, count)| ReactionCount { reaction, count })
+            .collect();
+        top_reactions.sort_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then_with(|| a.reaction.cmp(&b.reaction))
+        });
+        top_reactions.truncate(self.top_n);
+
+        ReactionStats {
+            reactions_count: self.reactions_count,
+            reacted_messages_count: self.reacted_messages_count,
+            top_reactions,
+            most_reacted_message: self.most_reacted_message,
+            most_reacted_message_reactions_count: self.most_reacted_count,
+            participants,
+        }
+    }
+}
+
+pub struct EntityAggregator {
+    top_n: usize,
+    entities_count: usize,
+    by_type: BTreeMap<String, usize>,
+    domains: HashMap<String, usize>,
+    links: HashMap<String, usize>,
+    mentions: HashMap<String, usize>,
+    hashtags: HashMap<String, usize>,
+    participants: BTreeMap<String, BTreeMap<String, usize>>,
+}
+
+impl EntityAggregator {
+    pub fn new(top_n: usize) -> Self {
+        Self {
+            top_n,
+            entities_count: 0,
+            by_type: BTreeMap::new(),
+            domains: HashMap::new(),
+            links: HashMap::new(),
+            mentions: HashMap::new(),
+            hashtags: HashMap::new(),
+            participants: BTreeMap::new(),
+        }
+    }
+
+    fn top(counts: HashMap<String, usize>, top_n: usize) -> Vec<EntityCount> {
+        let mut items: Vec<EntityCount> = counts
+            .into_iter()
+            .map(|(value, count)| EntityCount { value, count })
+            .collect();
+        items.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.value.cmp(&b.value)));
+        items.truncate(top_n);
+        items
+    }
+}
+
+impl Aggregator for EntityAggregator {
+    type Output = EntityStats;
+
+    fn observe(&mut self, message: &Message) {
+        const PLAIN: &str = "plain";
+
+        for entity in &message.text_entities {
+            if entity.r#type == PLAIN {
+                continue;
+            }
+
+            self.entities_count += 1;
+            *self.by_type.entry(entity.r#type.clone()).or_default() += 1;
+            if let Some(id) = &message.from_id {
+                *self
+                    .participants
+                    .entry(id.clone())
+                    .or_default()
+                    .entry(entity.r#type.clone())
+                    .or_default() += 1;
+            }
+
+            let link = match entity.r#type.as_str() {
+                "link" => Some(entity.text.trim()),
+                "text_link" => entity.href.as_deref(),
+                "mention" | "mention_name" => {
+                    // Упоминание без username ссылается на пользователя по id,
+                    // ключ совпадает с `from_id`, а не с отображаемым именем.
+                    // Username не зависит от регистра, как и хэштег.
+                    let mention = match entity.user_id {
+                        Some(user_id) => format!("user{user_id}"),
+                        None => entity.text.trim().to_lowercase(),
+                    };
+                    *self.mentions.entry(mention).or_default() += 1;
+                    None
+                }
+                "hashtag" => {
+                    *self
+                        .hashtags
+                        .entry(entity.text.trim().to_lowercase())
+                        .or_default() += 1;
+                    None
+                }
+                _ => None,
+            };
+            if let Some(link) = link {
+                *self.links.entry(link.to_string()).or_default() += 1;
+                if let Some(domain) = DataPreparer::link_domain(link) {
+                    *self.domains.entry(domain).or_default() += 1;
+                }
+            }
+        }
+    }
+
+    fn finish(self) -> Self::Output {
+        EntityStats {
+            entities_count: self.entities_count,
+            by_type: self.by_type,
+            top_domains: Self::top(self.domains, self.top_n),
+            top_links: Self::top(self.links, self.top_n),
+            top_mentions: Self::top(self.mentions, self.top_n),
+            top_hashtags: Self::top(self.hashtags, self.top_n),
+            participants: self.participants,
+        }
+    }
+}
+
+pub struct ForwardAggregator {
+    timezone: Tz,
+    top_n: usize,
+    total: ForwardShare,
+    forwarded_from: HashMap<String, usize>,
+    via_bots: HashMap<String, usize>,
+    participants: BTreeMap<String, ForwardShare>,
+    monthly: BTreeMap<NaiveDate, ForwardShare>,
+}
+
+impl ForwardAggregator {
+    pub fn new(timezone: Tz, top_n: usize) -> Self {
+        Self {
+            timezone,
+            top_n,
+            total: ForwardShare::default(),
+            forwarded_from: HashMap::new(),
+            via_bots: HashMap::new(),
+            participants: BTreeMap::new(),
+            monthly: BTreeMap::new(),
+        }
+    }
+
+    fn count(share: &mut ForwardShare, forwarded: bool, via_bot: bool) {
+        share.messages_count += 1;
+        if forwarded {
+            share.forwarded_count += 1;
+        }
+        if via_bot {
+            share.via_bot_count += 1;
+        }
+    }
+
+    fn with_share(mut share: ForwardShare) -> ForwardShare {
+        if share.messages_count > 0 {
+            share.forwarded_share = share.forwarded_count as f64 / share.messages_count as f64;
+        }
+        share
+    }
+
+    fn top(counts: HashMap<String, usize>, top_n: usize) -> Vec<SourceCount> {
+        let mut items: Vec<SourceCount> = counts
+            .into_iter()
+            .map(|(name, count)| SourceCount { name, count })
+            .collect();
+        items.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.name.cmp(&b.name)));
+        items.truncate(top_n);
+        items
+    }
+}
+
+impl Aggregator for ForwardAggregator {
+    type Output = ForwardStats;
+
+    fn observe(&mut self, message: &Message) {
+        if message.r#type != "message" {
+            return;
+        }
+
+        let source = message
+            .forwarded_from
+            .as_ref()
+            .or(message.saved_from.as_ref());
+        let forwarded = source.is_some();
+        let via_bot = message.via_bot.is_some();
+        let date = message.date.with_timezone(&self.timezone).date_naive();
+
+        Self::count(&mut self.total, forwarded, via_bot);
+        Self::count(
+            self.monthly
+                .entry(date.with_day(1).unwrap_or(date))
+                .or_default(),
+            forwarded,
+            via_bot,
+        );
+        if let Some(id) = &message.from_id {
+            Self::count(
+                self.participants.entry(id.clone()).or_default(),
+                forwarded,
+                via_bot,
+            );
+        }
+        if let Some(source) = source {
+            *self.forwarded_from.entry(source.clone()).or_default() += 1;
+        }
+        if let Some(bot) = &message.via_bot {
+            *self.via_bots.entry(bot.clone()).or_default() += 1;
+        }
+    }
+
+    fn finish(self) -> Self::Output {
+        let total = Self::with_share(self.total);
+
+        ForwardStats {
+            messages_count: total.messages_count,
+            forwarded_count: total.forwarded_count,
+            forwarded_share: total.forwarded_share,
+            via_bot_count: total.via_bot_count,
+            top_forwarded_from: Self::top(self.forwarded_from, self.top_n),
+            top_via_bots: Self::top(self.via_bots, self.top_n),
+            participants: self
+                .participants
+                .into_iter()
+                .map(|(id, share)| (id, Self::with_share(share)))
+                .collect(),
+            monthly: self
+                .monthly
+                .into_iter()
+                .map(|(month, share)| MonthlyForwards {
+                    month,
+                    share: Self::with_share(share),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Индекс, которого нет: у узла нет родителя или отправителя.
+const NO_INDEX: u32 = u32::MAX;
+
+/// Сколько последних сообщений держит [`ReplyGraphAggregator`], чтобы
+/// подставить их в результат без второго прохода.
+const RECENT_MESSAGES: usize = 1000;
+
+/// Узел графа ответов, 24 байта на сообщение.
+struct ReplyNode {
+    id: i64,
+    /// Индекс отправителя в `senders` или [`NO_INDEX`].
+    sender: u32,
+    /// Индекс родителя в `nodes` или [`NO_INDEX`] у начала ветки.
+    parent: u32,
+    depth: u32,
+    replies_count: u32,
+}
+
+/// Граф ответов по `reply_to_message_id`. Ответы на сообщения вне периода
+/// считаются началом ветки. На каждое сообщение хранится только компактный
+/// узел, а сообщения результата берутся из последних [`RECENT_MESSAGES`]
+/// в момент, когда они попадают в результат. Если нужное сообщение старше,
+/// в результате остаётся только его id и оно подставляется через
+/// [`ReplyGraphStats::resolve`].
+#[derive(Default)]
+pub struct ReplyGraphAggregator {
+    nodes: Vec<ReplyNode>,
+    index: HashMap<i64, u32>,
+    senders: Vec<String>,
+    sender_index: HashMap<String, u32>,
+    recent: VecDeque<MinimalMessage>,
+    replies_count: usize,
+    most_replied: Option<u32>,
+    most_replied_message: Option<MinimalMessage>,
+    deepest: Option<u32>,
+    /// Сообщения ветки до `deepest`, если все они нашлись.
+    deepest_thread: Option<Vec<MinimalMessage>>,
+    participants: BTreeMap<String, ParticipantReplies>,
+}
+
+impl ReplyGraphAggregator {
+    fn sender(&mut self, from_id: Option<&String>) -> u32 {
+        let Some(from_id) = from_id else {
+            return NO_INDEX;
+        };
+        if let Some(index) = self.sender_index.get(from_id) {
+            return *index;
+        }
+
+        let index = self.senders.len() as u32;
+        self.senders.push(from_id.clone());
+        self.sender_index.insert(from_id.clone(), index);
+        index
+    }
+
+    fn recent(&self, id: i64) -> Option<&MinimalMessage> {
+        self.recent.iter().rev().find(|message| message.id == id)
+    }
+
+    /// Ветка от начала до `node`, последнее сообщение ветки — `message`.
+    /// Остальные сообщения ищутся в `known` и среди последних.
+    fn thread(
+        &self,
+        node: u32,
+        message: &Message,
+        known: &[MinimalMessage],
+    ) -> Option<Vec<MinimalMessage>> {
+        let mut ids = Vec::new();
+        let mut next = self.nodes[node as usize].parent;
+        while next != NO_INDEX {
+            ids.push(self.nodes[next as usize].id);
+            next = self.nodes[next as usize].parent;
+        }
+
+        let mut thread = ids
+            .into_iter()
+            .rev()
+            .map(|id| {
+                known
+                    .iter()
+                    .find(|message| message.id == id)
+                    .or_else(|| self.recent(id))
+                    .cloned()
+            })
+            .collect::<Option<Vec<_>>>()?;
+        thread.push(message.into());
+        Some(thread)
+    }
+}
+
+impl Aggregator for ReplyGraphAggregator {
+    type Output = ReplyGraphStats;
+
+    fn observe(&mut self, message: &Message) {
+        if message.r#type != "message" {
+            return;
+        }
+
+        let node = self.nodes.len() as u32;
+        let parent = message
+            .reply_to_message_id
+            .and_then(|id| self.index.get(&id).copied())
+            .unwrap_or(NO_INDEX);
+        let sender = self.sender(message.from_id.as_ref());
+        let mut depth = 1;
+
+        if parent != NO_INDEX {
+            let parent_node = &mut self.nodes[parent as usize];
+            parent_node.replies_count += 1;
+            depth = parent_node.depth + 1;
+            let (parent_id, count) = (parent_node.id, parent_node.replies_count);
+            let replied_to = parent_node.sender;
+
+            // Побеждает больше ответов, при равенстве — более раннее сообщение
+            if self.most_replied.is_none_or(|best| {
+                let best = &self.nodes[best as usize];
+                count > best.replies_count || (count == best.replies_count && parent_id < best.id)
+            }) {
+                if self.most_replied != Some(parent) {
+                    self.most_replied_message = self.recent(parent_id).cloned();
+                }
+                self.most_replied = Some(parent);
+            }
+
+            if let Some(id) = message.from_id.as_ref().filter(|_| replied_to != NO_INDEX) {
+                let replied_to = &self.senders[replied_to as usize];
+                let participant = self.participants.entry(id.clone()).or_default();
+                *participant
+                    .replied_to
+                    .entry(replied_to.clone())
+                    .or_default() += 1;
+            }
+        }
+
+        if let Some(id) = &message.from_id {
+            let participant = self.participants.entry(id.clone()).or_default();
+            participant.messages_count += 1;
+            if message.reply_to_message_id.is_some() {
+                participant.replies_count += 1;
+            }
+        }
+        if message.reply_to_message_id.is_some() {
+            self.replies_count += 1;
+        }
+
+        self.nodes.push(ReplyNode {
+            id: message.id,
+            sender,
+            parent,
+            depth,
+            replies_count: 0,
+        });
+        self.index.insert(message.id, node);
+
+        if self
+            .deepest
+            .is_none_or(|deepest| depth > self.nodes[deepest as usize].depth)
+        {
+            self.deepest_thread = match self.deepest_thread.take() {
+                // Ветка продолжилась: достаточно добавить новое сообщение
+                Some(mut thread) if self.deepest == Some(parent) => {
+                    thread.push(message.into());
+                    Some(thread)
+                }
+                known => self.thread(node, message, known.as_deref().unwrap_or_default()),
+            };
+            self.deepest = Some(node);
+        }
+
+        if self.recent.len() == RECENT_MESSAGES {
+            self.recent.pop_front();
+        }
+        self.recent.push_back(message.into());
+    }
+
+    fn finish(self) -> Self::Output {
+        let mut participants = self.participants;
+        for participant in participants.values_mut() {
+            if participant.messages_count > 0 {
+                participant.reply_share =
+                    participant.replies_count as f64 / participant.messages_count as f64;
+            }
+            participant.most_replied_to = participant
+                .replied_to
+                .iter()
+                .max_by(|a, b| a.1.cmp(b.1).then_with(|| b.0.cmp(a.0)))
+                .map(|(id, _)| id.clone());
+        }
+
+        let most_replied = self.most_replied.map(|node| &self.nodes[node as usize]);
+
+        let mut deepest_thread_ids = Vec::new();
+        let mut next = self
+            .deepest
+            .filter(|node| self.nodes[*node as usize].depth > 1)
+            .unwrap_or(NO_INDEX);
+        while next != NO_INDEX {
+            deepest_thread_ids.push(self.nodes[next as usize].id);
+            next = self.nodes[next as usize].parent;
+        }
+        deepest_thread_ids.reverse();
+        let deepest_thread = match self.deepest_thread {
+            Some(thread) if !deepest_thread_ids.is_empty() => thread,
+            _ => vec![],
+        };
+
+        ReplyGraphStats {
+            replies_count: self.replies_count,
+            most_replied_message_id: most_replied.map(|node| node.id),
+            most_replied_message: self.most_replied_message,
+            most_replied_message_replies_count: most_replied
+                .map_or(0, |node| node.replies_count as usize),
+            deepest_thread_depth: deepest_thread_ids.len(),
+            deepest_thread_ids,
+            deepest_thread,
+            participants,
+        }
+    }
+}
+
+#[derive(Default)]
+struct EditLatencies {
+    messages_count: usize,
+    latencies: Vec<i64>,
+}
+
+impl EditLatencies {
+    fn share(&self) -> f64 {
+        match self.messages_count {
+            0 => 0.0,
+            count => self.latencies.len() as f64 / count as f64,
+        }
+    }
+}
+
+/// Отредактированные сообщения и задержка правки от отправки.
+#[derive(Default)]
+pub struct EditAggregator {
+    total: EditLatencies,
+    longest_edit: Option<EditedMessage>,
+    participants: BTreeMap<String, EditLatencies>,
+}
+
+impl EditAggregator {
+    /// Верхние границы корзин распределения задержек, в секундах.
+    const LATENCY_BUCKETS: [i64; 4] = [60, 10 * 60, 60 * 60, 24 * 60 * 60];
+}
+
+impl Aggregator for EditAggregator {
+    type Output = EditStats;
+
+    fn observe(&mut self, message: &Message) {
+        if message.r#type != "message" {
+            return;
+        }
+
+        let latency = message
+            .edited_unixtime
+            .as_deref()
+            .and_then(|edited| edited.parse::<i64>().ok())
+            .map(|edited| (edited - message.date.timestamp()).max(0));
+        let mut participant = message
+            .from_id
+            .as_ref()
+            .map(|id| self.participants.entry(id.clone()).or_default());
+
+        self.total.messages_count += 1;
+        if let Some(participant) = participant.as_mut() {
+            participant.messages_count += 1;
+        }
+        let Some(latency) = latency else {
+            return;
+        };
+
+        self.total.latencies.push(latency);
+        if let Some(participant) = participant {
+            participant.latencies.push(latency);
+        }
+        if self
+            .longest_edit
+            .as_ref()
+            .is_none_or(|longest| latency > longest.latency_sec)
+        {
+            self.longest_edit = Some(EditedMessage {
+                latency_sec: latency,
+                message: message.into(),
+            });
+        }
+    }
+
+    fn finish(mut self) -> Self::Output {
+        self.total.latencies.sort_unstable();
+        let latencies = &self.total.latencies;
+
+        let mut latency_distribution: Vec<EditLatencyBucket> = Self::LATENCY_BUCKETS
+            .iter()
+            .map(|&max_sec| EditLatencyBucket {
+                max_sec: Some(max_sec),
+                edited_count: 0,
+            })
+            .chain([EditLatencyBucket {
+                max_sec: None,
+                edited_count: 0,
+            }])
+            .collect();
+        for latency in latencies {
+            let bucket = Self::LATENCY_BUCKETS
+                .iter()
+                .position(|max_sec| latency < max_sec)
+                .unwrap_or(Self::LATENCY_BUCKETS.len());
+            latency_distribution[bucket].edited_count += 1;
+        }
+
+        EditStats {
+            edited_count: latencies.len(),
+            edited_share: self.total.share(),
+            median_latency_sec: ReplyTimes::percentile(latencies, 50),
+            p90_latency_sec: ReplyTimes::percentile(latencies, 90),
+            latency_distribution,
+            longest_edit: self.longest_edit,
+            participants: self
+                .participants
+                .into_iter()
+                .map(|(id, mut participant)| {
+                    participant.latencies.sort_unstable();
+                    let edits = ParticipantEdits {
+                        messages_count: participant.messages_count,
+                        edited_count: participant.latencies.len(),
+                        edited_share: participant.share(),
+                        median_latency_sec: ReplyTimes::percentile(&participant.latencies, 50),
+                    };
+                    (id, edits)
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Опросы. Голоса владельца экспорта известны по `chosen` в ответах.
+#[derive(Default)]
+pub struct PollAggregator {
+    polls_count: usize,
+    closed_count: usize,
+    total_voters: usize,
+    most_voted_poll: Option<MinimalMessage>,
+    most_voted_poll_voters_count: i32,
+    participants: BTreeMap<String, usize>,
+    voted_count: usize,
+    voted_for_winner_count: usize,
+}
+
+impl Aggregator for PollAggregator {
+    type Output = PollStats;
+
+    fn observe(&mut self, message: &Message) {
+        let Some(poll) = &message.poll else {
+            return;
+        };
+
+        self.polls_count += 1;
+        if poll.closed {
+            self.closed_count += 1;
+        }
+        self.total_voters += poll.total_voters.max(0) as usize;
+        if self.most_voted_poll.is_none() || poll.total_voters > self.most_voted_poll_voters_count {
+            self.most_voted_poll_voters_count = poll.total_voters;
+            self.most_voted_poll = Some(message.into());
+        }
+        if let Some(id) = &message.from_id {
+            *self.participants.entry(id.clone()).or_default() += 1;
+        }
+
+        // Победителей при равенстве голосов может быть несколько
+        let max_voters = poll.answers.iter().map(|answer| answer.voters).max();
+        if poll.answers.iter().any(|answer| answer.chosen) {
+            self.voted_count += 1;
+            if poll
+                .answers
+                .iter()
+                .any(|answer| answer.chosen && Some(answer.voters) == max_voters)
+            {
+                self.voted_for_winner_count += 1;
+            }
+        }
+    }
+
+    fn finish(self) -> Self::Output {
+        PollStats {
+            polls_count: self.polls_count,
+            closed_count: self.closed_count,
+            open_count: self.polls_count - self.closed_count,
+            total_voters: self.total_voters,
+            most_voted_poll: self.most_voted_poll,
+            most_voted_poll_voters_count: self.most_voted_poll_voters_count,
+            participants: self.participants,
+            owner_voted_count: self.voted_count,
+            owner_voted_for_winner_count: self.voted_for_winner_count,
+            owner_voted_for_winner_share: match self.voted_count {
+                0 => 0.0,
+                count => self.voted_for_winner_count as f64 / count as f64,
+            },
+        }
+    }
+}
+
+#[derive(Default)]
+pub struct StickerAggregator {
+    usage_counter: HashMap<String, i32>,
+    max_used: i32,
+    max_message: Option<MinimalMessage>,
+}
+
+impl Aggregator for StickerAggregator {
+    type Output = (i32, Option<MinimalMessage>);
+
+    fn observe(&mut self, message: &Message) {
+        if let Some(key) = DataPreparer::sticker_key(message) {
+            let count = self.usage_counter.entry(key).or_insert(0);
+            *count += 1;
+            if *count > self.max_used {
+                self.max_used = *count;
+                self.max_message = Some(message.into());
+            }
+        }
+    }
+
+    fn finish(self) -> Self::Output {
+        (self.max_used, self.max_message)
+    }
+}
+
+/// Топ стикеров и их эмодзи.
+pub struct StickerLeaderboardAggregator {
+    top_n: usize,
+    stickers_count: usize,
+    stickers: HashMap<String, (usize, MinimalMessage)>,
+    emoji: HashMap<String, usize>,
+}
+
+impl StickerLeaderboardAggregator {
+    pub fn new(top_n: usize) -> Self {
+        Self {
+            top_n,
+            stickers_count: 0,
+            stickers: HashMap::new(),
+            emoji: HashMap::new(),
+        }
+    }
+}
+
+impl Aggregator for StickerLeaderboardAggregator {
+    type Output = StickerStats;
+
+    fn observe(&mut self, message: &Message) {
+        let Some(key) = DataPreparer::sticker_key(message) else {
+            return;
+        };
+
+        self.stickers_count += 1;
+        self.stickers
+            .entry(key)
+            .or_insert_with(|| (0, message.into()))
+            .0 += 1;
+        if let Some(emoji) = &message.sticker_emoji {
+            *self.emoji.entry(emoji.clone()).or_default() += 1;
+        }
+    }
+
+    fn finish(self) -> Self::Output {
+        let unique_stickers_count = self.stickers.len();
+        let mut top_stickers: Vec<StickerCount> = self
+            .stickers
+            .into_values()
+            .map(|(count, message)| StickerCount { count, message })
+            .collect();
+        top_stickers.sort_by(|a, b| {
+            b.count
+                .cmp(&a.count)
+                .then_with(|| a.message.date.cmp(&b.message.date))
+        });
+        top_stickers.truncate(self.top_n);
+
+        let mut top_sticker_emoji: Vec<StickerEmojiCount> = self
+            .emoji
+            .into_iter()
+            .map(|(emoji, count)| StickerEmojiCount { emoji, count })
+            .collect();
+        top_sticker_emoji.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.emoji.cmp(&b.emoji)));
+        top_sticker_emoji.truncate(self.top_n);
+
+        StickerStats {
+            stickers_count: self.stickers_count,
+            unique_stickers_count,
+            top_stickers,
+            top_sticker_emoji,
+        }
+    }
+}
+
+pub struct StickersAggregator {
+    top_n: usize,
+    total: StickerLeaderboardAggregator,
+    participants: BTreeMap<String, StickerLeaderboardAggregator>,
+}
+
+impl StickersAggregator {
+    pub fn new(top_n: usize) -> Self {
+        Self {
+            top_n,
+            total: StickerLeaderboardAggregator::new(top_n),
+            participants: BTreeMap::new(),
+        }
+    }
+}
+
+impl Aggregator for StickersAggregator {
+    type Output = StickersStats;
+
+    fn observe(&mut self, message: &Message) {
+        self.total.observe(message);
+        if let Some(id) = &message.from_id {
+            self.participants
+                .entry(id.clone())
+                .or_insert_with(|| StickerLeaderboardAggregator::new(self.top_n))
+                .observe(message);
+        }
+    }
+
+    fn finish(self) -> Self::Output {
+        StickersStats {
+            total: self.total.finish(),
+            participants: self
+                .participants
+                .into_iter()
+                .map(|(id, participant)| (id, participant.finish()))
+                .collect(),
+        }
+    }
+}
+
+pub struct MostUsedStickerAggregator {
+    owner: ChatOwner,
+    owner_stickers: StickerAggregator,
+    member_stickers: StickerAggregator,
+}
+
+impl MostUsedStickerAggregator {
+    pub fn new(owner: ChatOwner) -> Self {
+        Self {
+            owner,
+            owner_stickers: StickerAggregator::default(),
+            member_stickers: StickerAggregator::default(),
+        }
+    }
+}
+
+impl Aggregator for MostUsedStickerAggregator {
+    type Output = MostUsedSticker;
+
+    fn observe(&mut self, message: &Message) {
+        if self.owner.is_owner(message) {
+            self.owner_stickers.observe(message);
+        } else if self.owner.is_member(message) {
+            self.member_stickers.observe(message);
+        }
+    }
+
+    fn finish(self) -> Self::Output {
+        let owner = self.owner_stickers.finish();
+        let member = self.member_stickers.finish();
+
+        MostUsedSticker {
+            owner_most_used_sticker_count: owner.0,
+            owner_most_used_sticker: owner.1,
+            member_most_used_sticker_count: member.0,
+            member_most_used_sticker: member.1,
+        }
+    }
+}
+
+pub struct EmojiAggregator {
+    top_n: usize,
+    messages_count: usize,
+    messages_with_emoji_count: usize,
+    counts: HashMap<String, i32>,
+}
+
+impl EmojiAggregator {
+    pub fn new(top_n: usize) -> Self {
+        Self {
+            top_n,
+            messages_count: 0,
+            messages_with_emoji_count: 0,
+            counts: HashMap::new(),
+        }
+    }
+}
+
+impl Aggregator for EmojiAggregator {
+    type Output = EmojiStats;
+
+    fn observe(&mut self, message: &Message) {
+        if message.r#type != "message" {
+            return;
+        }
+
+        let text = DataPreparer::message_text_to_string(&message.text);
+        let emojis = DataPreparer::extract_emojis(&text);
+        self.messages_count += 1;
+        if !emojis.is_empty() {
+            self.messages_with_emoji_count += 1;
+        }
+        for emoji in emojis {
+            *self.counts.entry(emoji).or_insert(0) += 1;
+        }
+    }
+
+    fn finish(self) -> Self::Output {
+        let emoji_count: i32 = self.counts.values().sum();
+        let share = |count: usize| match self.messages_count {
+            0 => 0.0,
+            total => count as f64 / total as f64,
+        };
+        let emoji_per_message = share(emoji_count as usize);
+        let messages_with_emoji_share = share(self.messages_with_emoji_count);
+
+        let mut top_emojis: Vec<EmojiCount> = self
+            .counts
+            .into_iter()
+            .map(|(emoji, count)| EmojiCount { emoji, count })
+            .collect();
+        top_emojis.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.emoji.cmp(&b.emoji)));
+        top_emojis.truncate(self.top_n);
+
+        EmojiStats {
+            top_emoji: top_emojis.first().map(|top| top.emoji.clone()),
+            top_emoji_count: top_emojis.first().map_or(0, |top| top.count),
+            emoji_count: emoji_count as usize,
+            top_emojis,
+            emoji_per_message,
+            messages_with_emoji_share,
+        }
+    }
+}
+
+pub struct WordsAggregator {
+    limit: usize,
+    counts: HashMap<String, i32>,
+}
+
+impl WordsAggregator {
+    pub fn new(limit: usize) -> Self {
+        Self {
+            limit,
+            counts: HashMap::new(),
+        }
+    }
+}
+
+impl Aggregator for WordsAggregator {
+    type Output = Vec<WordCount>;
+
+    fn observe(&mut self, message: &Message) {
+        let text = DataPreparer::message_text_to_string(&message.text);
+        for word in DataPreparer::extract_words(&text) {
+            *self.counts.entry(word).or_insert(0) += 1;
+        }
+    }
+
+    fn finish(self) -> Self::Output {
+        let mut items: Vec<WordCount> = self
+            .counts
+            .into_iter()
+            .map(|(word, count)| WordCount { word, count })
+            .collect();
+        items.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.word.cmp(&b.word)));
+        items.truncate(self.limit);
+        items
+    }
+}
+
+/// Самая длинная серия дней подряд с сообщениями. Дни считаются
+/// в часовом поясе `timezone`.
+pub struct StreakAggregator {
+    timezone: Tz,
+    max_streak: Option<Streak>,
+    current_streak: Option<Streak>,
+}
+
+impl StreakAggregator {
+    pub fn new(timezone: Tz) -> Self {
+        Self {
+            timezone,
+            max_streak: None,
+            current_streak: None,
+        }
+    }
+}
+
+impl Aggregator for StreakAggregator {
+    type Output = Streak;
+
+    fn observe(&mut self, message: &Message) {
+        let message_date = message.date.with_timezone(&self.timezone).date_naive();
+
+        match &mut self.current_streak {
+            None => {
+                self.current_streak = Some(Streak {
+                    count: 1,
+                    start: message_date,
+                    end: message_date,
+                });
+            }
+            Some(current) if message_date == current.end + Duration::days(1) => {
+                current.end = message_date;
+                current.count += 1;
+            }
+            Some(current) if message_date > current.end + Duration::days(1) => {
+                let current = std::mem::replace(
+                    current,
+                    Streak {
+                        count: 1,
+                        start: message_date,
+                        end: message_date,
+                    },
+                );
+                if self
+                    .max_streak
+                    .as_ref()
+                    .is_none_or(|max| current.count > max.count)
+                {
+                    self.max_streak = Some(current);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn finish(self) -> Self::Output {
+        let today = Utc::now().with_timezone(&self.timezone).date_naive();
+        let empty = Streak {
+            count: 0,
+            start: today,
+            end: today,
+        };
+
+        match (self.max_streak, self.current_streak) {
+            (Some(max), Some(current)) if current.count <= max.count => max,
+            (_, Some(current)) => current,
+            (max, None) => max.unwrap_or(empty),
+        }
+    }
+}
+
+/// Ряды по дням, неделям и месяцам. Копит только дни, недели и месяцы
+/// собираются из них в конце.
+pub struct TimelineAggregator {
+    owner: ChatOwner,
+    timezone: Tz,
+    days: BTreeMap<NaiveDate, TimelinePoint>,
+}
+
+impl TimelineAggregator {
+    pub fn new(owner: ChatOwner, timezone: Tz) -> Self {
+        Self {
+            owner,
+            timezone,
+            days: BTreeMap::new(),
+        }
+    }
+
+    fn empty_point(date: NaiveDate) -> TimelinePoint {
+        TimelinePoint {
+            date,
+            messages_count: 0,
+            owner_messages_count: 0,
+            member_messages_count: 0,
+            characters_count: 0,
+            calls_duration_sec: 0,
+            calls_duration_min: 0,
+            participants: BTreeMap::new(),
+        }
+    }
+
+    fn merge(into: &mut TimelinePoint, point: &TimelinePoint) {
+        into.messages_count += point.messages_count;
+        into.owner_messages_count += point.owner_messages_count;
+        into.member_messages_count += point.member_messages_count;
+        into.characters_count += point.characters_count;
+        into.calls_duration_sec += point.calls_duration_sec;
+        into.calls_duration_min = into.calls_duration_sec / 60;
+        for (id, count) in &point.participants {
+            *into.participants.entry(id.clone()).or_default() += count;
+        }
+    }
+
+    fn group_by(days: &[TimelinePoint], key: fn(NaiveDate) -> NaiveDate) -> Vec<TimelinePoint> {
+        let mut groups: Vec<TimelinePoint> = Vec::new();
+        for day in days {
+            let date = key(day.date);
+            match groups.last_mut() {
+                Some(group) if group.date == date => Self::merge(group, day),
+                _ => {
+                    let mut group = Self::empty_point(date);
+                    Self::merge(&mut group, day);
+                    groups.push(group);
+                }
+            }
+        }
+        groups
+    }
+
+    /// При равенстве побеждает более ранний отрезок.
+    fn busiest(points: &[TimelinePoint]) -> Option<TimelinePoint> {
+        points
+            .iter()
+            .filter(|point| point.messages_count > 0)
+            .rev()
+            .max_by_key(|point| point.messages_count)
+            .cloned()
+    }
+}
+
+impl Aggregator for TimelineAggregator {
+    type Output = TimelineStats;
+
+    fn observe(&mut self, message: &Message) {
+        let date = message.date.with_timezone(&self.timezone).date_naive();
+        let point = self
+            .days
+            .entry(date)
+            .or_insert_with(|| Self::empty_point(date));
+
+        point.messages_count += 1;
+        if self.owner.is_owner(message) {
+            point.owner_messages_count += 1;
+        } else if self.owner.is_member(message) {
+            point.member_messages_count += 1;
+        }
+        point.characters_count += DataPreparer::message_characters(message);
+        if message.action.as_deref() == Some("phone_call") {
+            point.calls_duration_sec += message.duration_seconds.unwrap_or(0).max(0) as u32;
+            point.calls_duration_min = point.calls_duration_sec / 60;
+        }
+        if let Some(id) = &message.from_id {
+            *point.participants.entry(id.clone()).or_default() += 1;
+        }
+    }
+
+    fn finish(mut self) -> Self::Output {
+        let daily: Vec<TimelinePoint> = match (
+            self.days.first_key_value().map(|(date, _)| *date),
+            self.days.last_key_value().map(|(date, _)| *date),
+        ) {
+            (Some(first), Some(last)) => first
+                .iter_days()
+                .take_while(|date| *date <= last)
+                .map(|date| {
+                    self.days
+                        .remove(&date)
+                        .unwrap_or_else(|| Self::empty_point(date))
+                })
+                .collect(),
+            _ => vec![],
+        };
+        let weekly = Self::group_by(&daily, |date| {
+            date - Duration::days(date.weekday().num_days_from_monday().into())
+        });
+        let monthly = Self::group_by(&daily, |date| date.with_day(1).unwrap_or(date));
+
+        TimelineStats {
+            busiest_day: Self::busiest(&daily),
+            busiest_week: Self::busiest(&weekly),
+            busiest_month: Self::busiest(&monthly),
+            daily,
+            weekly,
+            monthly,
+        }
+    }
+}
+
+/// Задержки ответов одного участника.
+struct ReplyTimes {
+    /// Задержка в секундах и час ответа, по одной записи на ответ.
+    delays: Vec<(u32, u8)>,
+    fastest: Option<Reply>,
+    slowest: Option<Reply>,
+}
+
+impl ReplyTimes {
+    fn new() -> Self {
+        Self {
+            delays: vec![],
+            fastest: None,
+            slowest: None,
+        }
+    }
+
+    /// Процентиль по методу ближайшего ранга, `delays` должны быть отсортированы.
+    fn percentile(delays: &[i64], percent: usize) -> Option<i64> {
+        Self::ranked(delays.iter().copied(), delays.len(), percent)
+    }
+
+    /// Процентиль из `count` отсортированных задержек, переданных итератором.
+    fn ranked<T: Into<i64>>(
+        mut delays: impl Iterator<Item = T>,
+        count: usize,
+        percent: usize,
+    ) -> Option<i64> {
+        let rank = (count * percent).div_ceil(100).max(1);
+        delays.nth(rank - 1).map(Into::into)
+    }
+
+    fn finish(mut self) -> ResponseTimes {
+        self.delays.sort_unstable();
+
+        let delays = || self.delays.iter().map(|(delay, _)| *delay);
+        let mut hourly_counts = [0; 24];
+        for (_, hour) in &self.delays {
+            hourly_counts[*hour as usize] += 1;
+        }
+
+        ResponseTimes {
+            replies_count: self.delays.len(),
+            median_sec: Self::ranked(delays(), self.delays.len(), 50),
+            p90_sec: Self::ranked(delays(), self.delays.len(), 90),
+            by_hour: hourly_counts
+                .into_iter()
+                .enumerate()
+                .map(|(hour, count)| {
+                    let delays = self
+                        .delays
+                        .iter()
+                        .filter(|(_, reply_hour)| *reply_hour as usize == hour)
+                        .map(|(delay, _)| *delay);
+                    HourlyResponseTimes {
+                        hour: hour as u32,
+                        replies_count: count,
+                        median_sec: Self::ranked(delays, count, 50),
+                    }
+                })
+                .collect(),
+            fastest: self.fastest,
+            slowest: self.slowest,
+        }
+    }
+}
+
+/// Время ответа каждого участника на сообщения остальных.
+pub struct ResponseTimeAggregator {
+    timezone: Tz,
+    max_gap: TimeDelta,
+    last: Option<(String, DateTime<Utc>)>,
+    participants: BTreeMap<String, ReplyTimes>,
+}
+
+impl ResponseTimeAggregator {
+    pub fn new(timezone: Tz, max_gap: TimeDelta) -> Self {
+        Self {
+            timezone,
+            max_gap,
+            last: None,
+            participants: BTreeMap::new(),
+        }
+    }
+}
+
+impl Aggregator for ResponseTimeAggregator {
+    type Output = ResponseTimeStats;
+
+    fn observe(&mut self, message: &Message) {
+        let Some(id) = &message.from_id else {
+            return;
+        };
+
+        if let Some((last_id, last_date)) = &self.last {
+            let delay = message.date - *last_date;
+            // Отрицательная задержка бывает только у сообщений не по порядку
+            let seconds = u32::try_from(delay.num_seconds())
+                .ok()
+                .filter(|_| last_id != id && delay <= self.max_gap);
+            if let Some(seconds) = seconds {
+                let hour = message.date.with_timezone(&self.timezone).hour() as u8;
+                let times = self
+                    .participants
+                    .entry(id.clone())
+                    .or_insert_with(ReplyTimes::new);
+
+                times.delays.push((seconds, hour));
+                let seconds = i64::from(seconds);
+                if times
+                    .fastest
+                    .as_ref()
+                    .is_none_or(|fastest| seconds < fastest.seconds)
+                {
+                    times.fastest = Some(Reply {
+                        seconds,
+                        message: message.into(),
+                    });
+                }
+                if times
+                    .slowest
+                    .as_ref()
+                    .is_none_or(|slowest| seconds > slowest.seconds)
+                {
+                    times.slowest = Some(Reply {
+                        seconds,
+                        message: message.into(),
+                    });
+                }
+            }
+        }
+
+        self.last = Some((id.clone(), message.date));
+    }
+
+    fn finish(self) -> Self::Output {
+        ResponseTimeStats {
+            max_reply_gap_sec: self.max_gap.num_seconds(),
+            participants: self
+                .participants
+                .into_iter()
+                .map(|(id, times)| (id, times.finish()))
+                .collect(),
+        }
+    }
+}
+
+/// Тепловая карта сообщений по дням недели и часам.
+pub struct ActivityAggregator {
+    timezone: Tz,
+    heatmap: [[u32; 24]; 7],
+}
+
+impl ActivityAggregator {
+    pub fn new(timezone: Tz) -> Self {
+        Self {
+            timezone,
+            heatmap: [[0; 24]; 7],
+        }
+    }
+}
+
+impl Aggregator for ActivityAggregator {
+    type Output = Activity;
+
+    fn observe(&mut self, message: &Message) {
+        let date = message.date.with_timezone(&self.timezone);
+        self.heatmap[date.weekday().num_days_from_monday() as usize][date.hour() as usize] += 1;
+    }
+
+    fn finish(self) -> Self::Output {
+        const WEEKDAYS: [&str; 7] = [
+            "monday",
+            "tuesday",
+            "wednesday",
+            "thursday",
+            "friday",
+            "saturday",
+            "sunday",
+        ];
+        const NIGHT_HOURS: std::ops::Range<usize> = 0..6;
+
+        let by_hour: Vec<u32> = (0..24)
+            .map(|hour| self.heatmap.iter().map(|day| day[hour]).sum())
+            .collect();
+        let by_weekday: Vec<u32> = self.heatmap.iter().map(|day| day.iter().sum()).collect();
+        let total: u32 = by_hour.iter().sum();
+
+        // При равенстве побеждает более ранний час или день
+        let busiest = |counts: &[u32]| {
+            counts
+                .iter()
+                .enumerate()
+                .filter(|(_, count)| **count > 0)
+                .max_by(|(a_index, a), (b_index, b)| a.cmp(b).then(b_index.cmp(a_index)))
+                .map(|(index, _)| index)
+        };
+
+        Activity {
+            heatmap: self.heatmap,
+            busiest_hour: busiest(&by_hour).map(|hour| hour as u32),
+            most_active_weekday: busiest(&by_weekday).map(|day| WEEKDAYS[day].to_string()),
+            night_owl_share: match total {
+                0 => 0.0,
+                total => by_hour[NIGHT_HOURS].iter().sum::<u32>() as f64 / total as f64,
+            },
+        }
+    }
+}
+
+/// Тепловая карта для всего чата и для каждого отправителя.
+pub struct ActivityStatsAggregator {
+    total: ActivityAggregator,
+    participants: BTreeMap<String, ActivityAggregator>,
+}
+
+impl ActivityStatsAggregator {
+    pub fn new(timezone: Tz) -> Self {
+        Self {
+            total: ActivityAggregator::new(timezone),
+            participants: BTreeMap::new(),
+        }
+    }
+}
+
+impl Aggregator for ActivityStatsAggregator {
+    type Output = ActivityStats;
+
+    fn observe(&mut self, message: &Message) {
+        let Some(id) = &message.from_id else {
+            return;
+        };
+
+        self.total.observe(message);
+        self.participants
+            .entry(id.clone())
+            .or_insert_with(|| ActivityAggregator::new(self.total.timezone))
+            .observe(message);
+    }
+
+    fn finish(self) -> Self::Output {
+        ActivityStats {
+            total: self.total.finish(),
+            participants: self
+                .participants
+                .into_iter()
+                .map(|(id, participant)| (id, participant.finish()))
+                .collect(),
+        }
+    }
+}
+
+pub struct ParticipantAggregator {
+    name: Option<String>,
+    last_date: Option<DateTime<Utc>>,
+    messages_count: usize,
+    characters_count: usize,
+    stickers: StickerAggregator,
+    emoji: EmojiAggregator,
+    words: WordsAggregator,
+}
+
+impl ParticipantAggregator {
+    pub fn new(top_n: usize) -> Self {
+        Self {
+            name: None,
+            last_date: None,
+            messages_count: 0,
+            characters_count: 0,
+            stickers: StickerAggregator::default(),
+            emoji: EmojiAggregator::new(top_n),
+            words: WordsAggregator::new(5),
+        }
+    }
+}
+
+impl Aggregator for ParticipantAggregator {
+    type Output = ParticipantStats;
+
+    fn observe(&mut self, message: &Message) {
+        if self.last_date.is_none_or(|date| message.date >= date) {
+            self.last_date = Some(message.date);
+            self.name = message.from.clone();
+        }
+
+        self.messages_count += 1;
+        self.characters_count += DataPreparer::message_characters(message);
+        self.stickers.observe(message);
+        self.emoji.observe(message);
+        self.words.observe(message);
+    }
+
+    fn finish(self) -> Self::Output {
+        let (most_used_sticker_count, most_used_sticker) = self.stickers.finish();
+        ParticipantStats {
+            name: self.name,
+            messages_count: self.messages_count,
+            characters_count: self.characters_count,
+            most_used_sticker_count,
+            most_used_sticker,
+            emoji: self.emoji.finish(),
+            top_words: self.words.finish(),
+        }
+    }
+}
+
+pub struct ParticipantsAggregator {
+    top_n: usize,
+    participants: BTreeMap<String, ParticipantAggregator>,
+}
+
+impl ParticipantsAggregator {
+    pub fn new(top_n: usize) -> Self {
+        Self {
+            top_n,
+            participants: BTreeMap::new(),
+        }
+    }
+}
+
+impl Aggregator for ParticipantsAggregator {
+    type Output = BTreeMap<String, ParticipantStats>;
+
+    fn observe(&mut self, message: &Message) {
+        if let Some(id) = &message.from_id {
+            self.participants
+                .entry(id.clone())
+                .or_insert_with(|| ParticipantAggregator::new(self.top_n))
+                .observe(message);
+        }
+    }
+
+    fn finish(self) -> Self::Output {
+        self.participants
+            .into_iter()
+            .map(|(id, participant)| (id, participant.finish()))
+            .collect()
+    }
+}
+
+/// Собирает [`AllStats`] по одному чату за один проход по сообщениям.
+pub struct AllStatsAggregator {
+    header: ChatHeader,
+    options: StatsOptions,
+    source_dir: String,
+    owner: ChatOwner,
+    owner_id: Option<String>,
+    messages_stats: MessagesStatsAggregator,
+    additional_messages_stats: AdditionalMessagesStatsAggregator,
+    timeline: TimelineAggregator,
+    occurrences: Filtered<MessagesStatsAggregator>,
+    conversations: ConversationsAggregator,
+    calls_stats: CallsAggregator,
+    most_used_sticker: MostUsedStickerAggregator,
+    emoji_stats: EmojiAggregator,
+    word_stats: WordsAggregator,
+    streak: StreakAggregator,
+    participants: ParticipantsAggregator,
+    activity: ActivityStatsAggregator,
+    response_times: ResponseTimeAggregator,
+    media: MediaAggregator,
+    stickers: StickersAggregator,
+    reactions: ReactionAggregator,
+    entities: EntityAggregator,
+    forwards: ForwardAggregator,
+    replies: ReplyGraphAggregator,
+    edits: EditAggregator,
+    polls: PollAggregator,
+}
+
+impl AllStatsAggregator {
+    pub fn new(
+        header: ChatHeader,
+        options: StatsOptions,
+        source_dir: &str,
+        owner: ChatOwner,
+    ) -> Self {
+        const PERSONAL_CHAT: &str = "personal_chat";
+        let personal = header.r#type == PERSONAL_CHAT;
+
+        Self {
+            header,
+            options,
+            source_dir: source_dir.to_string(),
+            owner_id: match &owner {
+                ChatOwner::Id(id) => Some(id.clone()),
+                ChatOwner::NotPeer(_) => None,
+            },
+            messages_stats: MessagesStatsAggregator::new(owner.clone()),
+            additional_messages_stats: AdditionalMessagesStatsAggregator::new(owner.clone()),
+            timeline: TimelineAggregator::new(owner.clone(), options.range.timezone),
+            occurrences: Filtered::new(MessagesStatsAggregator::new(owner.clone()), |message| {
+                DataPreparer::text_matches(&message.text, &OCCURRENCES_RE)
+            }),
+            conversations: ConversationsAggregator::new(options.conversation_gap, options.top_n),
+            calls_stats: CallsAggregator::new(options.range.timezone, personal),
+            most_used_sticker: MostUsedStickerAggregator::new(owner.clone()),
+            emoji_stats: EmojiAggregator::new(options.top_n),
+            word_stats: WordsAggregator::new(5),
+            streak: StreakAggregator::new(options.range.timezone),
+            participants: ParticipantsAggregator::new(options.top_n),
+            activity: ActivityStatsAggregator::new(options.range.timezone),
+            response_times: ResponseTimeAggregator::new(
+                options.range.timezone,
+                options.max_reply_gap,
+            ),
+            media: MediaAggregator::default(),
+            stickers: StickersAggregator::new(options.top_n),
+            reactions: ReactionAggregator::new(options.top_n),
+            entities: EntityAggregator::new(options.top_n),
+            forwards: ForwardAggregator::new(options.range.timezone, options.top_n),
+            replies: ReplyGraphAggregator::default(),
+            edits: EditAggregator::default(),
+            polls: PollAggregator::default(),
+            owner,
+        }
+    }
+}
+
+impl AllStatsAggregator {
+    /// Самый длинный по числу сообщений разговор из топа разговоров.
+    fn longest_conversation(conversations: &ConversationStats, owner: &ChatOwner) -> MessagesStats {
+        let Some(longest) = conversations.longest_by_messages.first() else {
+            return MessagesStats {
+                first_message: None,
+                last_message: None,
+                total_messages_count: 0,
+                owner_messages_count: 0,
+                member_messages_count: 0,
+            };
+        };
+
+        let (owner_messages, member_messages): (Vec<_>, Vec<_>) = longest
+            .participants
+            .iter()
+            .partition(|(id, _)| owner.is_owner_id(id));
+        MessagesStats {
+            first_message: Some(longest.first_message.clone()),
+            last_message: Some(longest.last_message.clone()),
+            total_messages_count: longest.messages_count,
+            owner_messages_count: owner_messages.iter().map(|(_, count)| **count).sum(),
+            member_messages_count: member_messages.iter().map(|(_, count)| **count).sum(),
+        }
+    }
+}
+
+impl Aggregator for AllStatsAggregator {
+    type Output = AllStats;
+
+    fn observe(&mut self, message: &Message) {
+        if self.owner_id.is_none() && self.owner.is_owner(message) {
+            self.owner_id = message.from_id.clone();
+        }
+
+        self.messages_stats.observe(message);
+        self.additional_messages_stats.observe(message);
+        self.timeline.observe(message);
+        self.occurrences.observe(message);
+        self.conversations.observe(message);
+        self.calls_stats.observe(message);
+        self.most_used_sticker.observe(message);
+        self.emoji_stats.observe(message);
+        self.word_stats.observe(message);
+        self.streak.observe(message);
+        self.participants.observe(message);
+        self.activity.observe(message);
+        self.response_times.observe(message);
+        self.media.observe(message);
+        self.stickers.observe(message);
+        self.reactions.observe(message);
+        self.entities.observe(message);
+        self.forwards.observe(message);
+        self.replies.observe(message);
+        self.edits.observe(message);
+        self.polls.observe(message);
+    }
+
+    fn finish(self) -> Self::Output {
+        let messages_stats = self.messages_stats.finish();
+        let period = self.options.range.period(
+            messages_stats
+                .first_message
+                .as_ref()
+                .map(|message| message.date),
+            messages_stats
+                .last_message
+                .as_ref()
+                .map(|message| message.date),
+        );
+        let conversations = self.conversations.finish();
+        let avg_messages_per_day = match period.days {
+            0 => 0.0,
+            days => messages_stats.total_messages_count as f64 / days as f64,
+        };
+
+        AllStats {
+            chat_id: self.header.id,
+            chat_name: self.header.name,
+            chat_type: self.header.r#type,
+            year: self.options.range.calendar_year(),
+            period,
+            source_dir: self.source_dir,
+            owner_id: self.owner_id.unwrap_or_default(),
+            chat_stats: ChatStats {
+                messages_stats,
+                additional_messages_stats: self.additional_messages_stats.finish(),
+            },
+            timeline: self.timeline.finish(),
+            occurrences: self.occurrences.finish(),
+            longest_conversation: Self::longest_conversation(&conversations, &self.owner),
+            conversations,
+            calls_stats: self.calls_stats.finish(),
+            most_used_sticker: self.most_used_sticker.finish(),
+            emoji_stats: self.emoji_stats.finish(),
+            word_stats: WordStats {
+                top_words: self.word_stats.finish(),
+            },
+            avg_messages_per_day,
+            streak: self.streak.finish(),
+            participants: self.participants.finish(),
+            activity: self.activity.finish(),
+            response_times: self.response_times.finish(),
+            media: self.media.finish(),
+            stickers: self.stickers.finish(),
+            reactions: self.reactions.finish(),
+            entities: self.entities.finish(),
+            forwards: self.forwards.finish(),
+            replies: self.replies.finish(),
+            edits: self.edits.finish(),
+            polls: self.polls.finish(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::domain::types::chat::{MessageEntity, Poll, PollAnswer};
+    use chrono::TimeZone;
+
+    fn call(from: &str, reason: &str, duration: i32) -> Message {
+        Message {
+            date: Utc.with_ymd_and_hms(2024, 10, 1, 12, 0, 0).unwrap(),
+            actor_id: Some(from.to_string()),
+            action: Some("phone_call".to_string()),
+            discard_reason: Some(reason.to_string()),
+            duration_seconds: Some(duration),
+            ..Default::default()
+        }
+    }
+
+    /// Сообщение `from` через `minutes` минут после 12:00 UTC 1 октября 2024.
+    fn sent(from: &str, minutes: i64) -> Message {
+        Message {
+            id: minutes,
+            r#type: "message".to_string(),
+            date: Utc.with_ymd_and_hms(2024, 10, 1, 12, 0, 0).unwrap() + Duration::minutes(minutes),
+            from_id: Some(from.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn entity(r#type: &str, text: &str, user_id: Option<i64>) -> MessageEntity {
+        MessageEntity {
+            r#type: r#type.to_string(),
+            text: text.to_string(),
+            href: None,
+            user_id,
+        }
+    }
+
+    #[test]
+    fn mentions_are_keyed_by_user_id_or_lowercase_username() {
+        let message = Message {
+            from_id: Some("user1".to_string()),
+            text_entities: vec![
+                entity("mention", "@bob", None),
+                entity("mention", "@Bob", None),
+                entity("mention_name", "Bob", Some(222)),
+                entity("mention_name", "Bobby", Some(222)),
+                entity("mention_name", "Bobby", Some(333)),
+            ],
+            ..Default::default()
+        };
+        let stats = EntityAggregator::new(10).aggregate([&message]);
+
+        let mentions: Vec<_> = stats
+            .top_mentions
+            .iter()
+            .map(|mention| (mention.value.as_str(), mention.count))
+            .collect();
+        assert_eq!(mentions, [("@bob", 2), ("user222", 2), ("user333", 1)]);
+    }
+
+    #[test]
+    fn response_times_store_each_reply_once() {
+        let messages = [
+            sent("user1", 0),
+            sent("user2", 1),
+            sent("user2", 2),
+            sent("user1", 5),
+            sent("user2", 65),
+            // Не по порядку: отрицательная задержка не считается ответом
+            sent("user1", 60),
+            // Больше `max_gap`
+            sent("user2", 200),
+        ];
+        let stats = ResponseTimeAggregator::new(Tz::UTC, TimeDelta::hours(1)).aggregate(&messages);
+
+        let first = &stats.participants["user1"];
+        assert_eq!(first.replies_count, 1);
+        assert_eq!(first.median_sec, Some(180));
+
+        let second = &stats.participants["user2"];
+        assert_eq!(second.replies_count, 2);
+        assert_eq!(second.median_sec, Some(60));
+        assert_eq!(second.p90_sec, Some(3600));
+        assert_eq!(
+            second.fastest.as_ref().map(|reply| reply.message.id),
+            Some(1)
+        );
+        assert_eq!(
+            second.slowest.as_ref().map(|reply| reply.message.id),
+            Some(65)
+        );
+        assert_eq!(second.by_hour[12].replies_count, 1);
+        assert_eq!(second.by_hour[12].median_sec, Some(60));
+        assert_eq!(second.by_hour[13].replies_count, 1);
+        assert_eq!(second.by_hour[13].median_sec, Some(3600));
+        assert_eq!(second.by_hour[14].median_sec, None);
+    }
+
+    fn reply(from: &str, minutes: i64, to: i64) -> Message {
+        Message {
+            reply_to_message_id: Some(to),
+            ..sent(from, minutes)
+        }
+    }
+
+    #[test]
+    fn reply_to_message_outside_range_starts_thread() {
+        let messages = [
+            reply("user1", 1, 100_000),
+            reply("user2", 2, 1),
+            sent("user1", 3),
+        ];
+        let stats = ReplyGraphAggregator::default().aggregate(&messages);
+
+        assert_eq!(stats.replies_count, 2);
+        assert_eq!(stats.deepest_thread_ids, [1, 2]);
+        assert_eq!(stats.deepest_thread_depth, 2);
+        assert_eq!(stats.most_replied_message_id, Some(1));
+        assert_eq!(stats.participants["user2"].replied_to["user1"], 1);
+    }
+
+    #[test]
+    fn reply_graph_captures_messages_in_one_pass() {
+        let messages = [
+            sent("user1", 1),
+            reply("user2", 2, 1),
+            sent("user1", 3),
+            reply("user1", 4, 2),
+            reply("user2", 5, 3),
+            reply("user1", 6, 1),
+            reply("user2", 7, 4),
+        ];
+        let stats = ReplyGraphAggregator::default().aggregate(&messages);
+
+        assert!(stats.is_resolved());
+        assert_eq!(stats.replies_count, 5);
+        assert_eq!(stats.most_replied_message_id, Some(1));
+        assert_eq!(stats.most_replied_message.as_ref().map(|m| m.id), Some(1));
+        assert_eq!(stats.most_replied_message_replies_count, 2);
+        assert_eq!(stats.deepest_thread_ids, [1, 2, 4, 7]);
+        let thread: Vec<_> = stats.deepest_thread.iter().map(|m| m.id).collect();
+        assert_eq!(thread, [1, 2, 4, 7]);
+        assert_eq!(stats.participants["user1"].replied_to["user2"], 1);
+        assert_eq!(stats.participants["user2"].replied_to["user1"], 3);
+    }
+
+    #[test]
+    fn reply_graph_resolves_old_messages_after_the_pass() {
+        let mut messages: Vec<_> = (1..=RECENT_MESSAGES as i64 + 1)
+            .map(|minutes| sent("user1", minutes))
+            .collect();
+        messages.push(reply("user2", 5000, 1));
+        let mut stats = ReplyGraphAggregator::default().aggregate(&messages);
+
+        assert!(!stats.is_resolved());
+        assert_eq!(stats.most_replied_message_id, Some(1));
+        assert_eq!(stats.deepest_thread_ids, [1, 5000]);
+
+        for message in &messages {
+            stats.resolve(message);
+        }
+        assert!(stats.is_resolved());
+        let thread: Vec<_> = stats.deepest_thread.iter().map(|m| m.id).collect();
+        assert_eq!(thread, [1, 5000]);
+    }
+
+    #[test]
+    fn media_ignores_service_messages() {
+        let messages = [
+            Message {
+                r#type: "service".to_string(),
+                action: Some("edit_group_photo".to_string()),
+                photo: Some("photos/photo_1.jpg".to_string()),
+                ..Default::default()
+            },
+            Message {
+                r#type: "message".to_string(),
+                from_id: Some("user1".to_string()),
+                photo: Some("photos/photo_2.jpg".to_string()),
+                ..Default::default()
+            },
+        ];
+        let stats = MediaAggregator::default().aggregate(&messages);
+
+        assert_eq!(stats.media_count, 1);
+        assert_eq!(stats.by_type.get("photo"), Some(&1));
+    }
+
+    #[test]
+    fn personal_chat_calls_count_incoming_from_peer() {
+        let messages = [
+            call("user1", "hangup", 60),
+            call("user1", "missed", 0),
+            call("user2", "hangup", 30),
+        ];
+        let stats = CallsAggregator::new(Tz::UTC, true).aggregate(&messages);
+
+        let first = &stats.participants["user1"];
+        assert_eq!(first.outgoing_count, 2);
+        assert_eq!(first.incoming_count, Some(1));
+        assert_eq!(first.missed_incoming_count, Some(0));
+
+        let second = &stats.participants["user2"];
+        assert_eq!(second.outgoing_count, 1);
+        assert_eq!(second.incoming_count, Some(2));
+        assert_eq!(second.missed_incoming_count, Some(1));
+    }
+
+    #[test]
+    fn group_chat_calls_leave_incoming_unknown() {
+        let messages = [
+            sent("user3", 0),
+            call("user1", "hangup", 60),
+            call("user2", "missed", 0),
+        ];
+        let stats = CallsAggregator::new(Tz::UTC, false).aggregate(&messages);
+
+        assert_eq!(stats.calls_count, 2);
+        for participant in stats.participants.values() {
+            assert_eq!(participant.incoming_count, None);
+            assert_eq!(participant.missed_incoming_count, None);
+        }
+        assert_eq!(stats.participants["user1"].outgoing_count, 1);
+        assert_eq!(stats.participants["user2"].unanswered_outgoing_count, 1);
+        assert_eq!(stats.participants["user3"].outgoing_count, 0);
+    }
+
+    #[test]
+    fn conversations_are_split_by_gap_and_ranked() {
+        let messages = [
+            sent("user1", 0),
+            sent("user2", 5),
+            // Короче предыдущего по времени, но длиннее по сообщениям
+            sent("user2", 30),
+            sent("user1", 31),
+            sent("user2", 32),
+            sent("user2", 33),
+            // Ровно через `gap` после предыдущего сообщения
+            sent("user1", 48),
+            sent("user2", 49),
+            sent("user1", 50),
+        ];
+        let stats = ConversationsAggregator::new(TimeDelta::minutes(15), 2).aggregate(&messages);
+
+        assert_eq!(stats.conversations_count, 3);
+        assert_eq!(stats.avg_messages_count, 3.0);
+        assert_eq!(stats.avg_duration_sec, 200.0);
+
+        let by_messages: Vec<_> = stats
+            .longest_by_messages
+            .iter()
+            .map(|conversation| (conversation.first_message.id, conversation.messages_count))
+            .collect();
+        assert_eq!(by_messages, [(30, 4), (48, 3)]);
+
+        let by_duration: Vec<_> = stats
+            .longest_by_duration
+            .iter()
+            .map(|conversation| (conversation.first_message.id, conversation.duration_sec))
+            .collect();
+        assert_eq!(by_duration, [(0, 300), (30, 180)]);
+        assert_eq!(stats.longest_by_messages[0].participants["user2"], 3);
+    }
+
+    #[test]
+    fn conversation_initiative_counts_double_texts() {
+        let messages = [
+            sent("user1", 0),
+            sent("user2", 5),
+            // user2 закончил прошлый разговор и начинает новый
+            sent("user2", 30),
+            sent("user1", 31),
+            sent("user2", 32),
+            sent("user1", 60),
+            sent("user2", 61),
+            sent("user1", 62),
+            // Без ответа на прошлое сообщение
+            sent("user1", 90),
+        ];
+        let stats = ConversationsAggregator::new(TimeDelta::minutes(15), 10).aggregate(&messages);
+
+        let first = &stats.participants["user1"];
+        assert_eq!(first.started_count, 3);
+        assert_eq!(first.started_share, 0.75);
+        assert_eq!(first.ended_count, 2);
+        assert_eq!(first.ended_share, 0.5);
+        assert_eq!(first.double_texts_count, 1);
+        assert_eq!(first.double_texts_share, 1.0 / 3.0);
+
+        let second = &stats.participants["user2"];
+        assert_eq!(second.started_count, 1);
+        assert_eq!(second.ended_count, 2);
+        assert_eq!(second.double_texts_count, 1);
+        assert_eq!(second.double_texts_share, 1.0);
+    }
+
+    /// Сообщение, отредактированное через `after_sec` секунд после отправки.
+    fn edited(from: &str, minutes: i64, after_sec: i64) -> Message {
+        let mut message = sent(from, minutes);
+        message.edited_unixtime = Some((message.date.timestamp() + after_sec).to_string());
+        message
+    }
+
+    #[test]
+    fn edit_latencies_are_bucketed_per_participant() {
+        let mut service = edited("user1", 5, 100_000);
+        service.r#type = "service".to_string();
+        let messages = [
+            edited("user1", 0, 30),
+            sent("user1", 1),
+            edited("user2", 2, 2 * 60 * 60),
+            edited("user2", 3, 5 * 60),
+            // Правка раньше отправки из-за расхождения часов
+            edited("user1", 4, -10),
+            service,
+        ];
+        let stats = EditAggregator::default().aggregate(&messages);
+
+        assert_eq!(stats.edited_count, 4);
+        assert_eq!(stats.edited_share, 0.8);
+        assert_eq!(stats.median_latency_sec, Some(30));
+        assert_eq!(stats.p90_latency_sec, Some(7200));
+
+        let distribution: Vec<_> = stats
+            .latency_distribution
+            .iter()
+            .map(|bucket| bucket.edited_count)
+            .collect();
+        assert_eq!(distribution, [2, 1, 0, 1, 0]);
+
+        let longest = stats.longest_edit.unwrap();
+        assert_eq!((longest.message.id, longest.latency_sec), (2, 7200));
+
+        let first = &stats.participants["user1"];
+        assert_eq!((first.messages_count, first.edited_count), (3, 2));
+        assert_eq!(first.median_latency_sec, Some(0));
+
+        let second = &stats.participants["user2"];
+        assert_eq!(second.edited_share, 1.0);
+        assert_eq!(second.median_latency_sec, Some(300));
+    }
+
+    /// Опрос с ответами `(voters, chosen)`.
+    fn poll(from: &str, minutes: i64, closed: bool, answers: &[(i32, bool)]) -> Message {
+        Message {
+            poll: Some(Poll {
+                question: "?".to_string(),
+                closed,
+                total_voters: answers.iter().map(|(voters, _)| voters).sum(),
+                answers: answers
+                    .iter()
+                    .map(|&(voters, chosen)| PollAnswer {
+                        text: voters.to_string(),
+                        voters,
+                        chosen,
+                    })
+                    .collect(),
+            }),
+            ..sent(from, minutes)
+        }
+    }
+
+    #[test]
+    fn polls_count_owner_votes_for_winner() {
+        let messages = [
+            poll("user1", 0, true, &[(3, false), (1, true)]),
+            // Ничья: оба ответа считаются победившими
+            poll("user2", 1, false, &[(2, true), (2, false)]),
+            poll("user1", 2, false, &[(5, false), (1, false)]),
+            sent("user2", 3),
+        ];
+        let stats = PollAggregator::default().aggregate(&messages);
+
+        assert_eq!(stats.polls_count, 3);
+        assert_eq!((stats.closed_count, stats.open_count), (1, 2));
+        assert_eq!(stats.total_voters, 14);
+        assert_eq!(stats.most_voted_poll.map(|poll| poll.id), Some(2));
+        assert_eq!(stats.most_voted_poll_voters_count, 6);
+        assert_eq!(stats.participants["user1"], 2);
+        assert_eq!(stats.participants["user2"], 1);
+        assert_eq!(stats.owner_voted_count, 2);
+        assert_eq!(stats.owner_voted_for_winner_count, 1);
+        assert_eq!(stats.owner_voted_for_winner_share, 0.5);
+    }
+}
@@ -1,20 +1,22 @@
-use crate::infrastructure::repositories::data_repository::{DataRepository, Result};
+use crate::infrastructure::repositories::data_repository::{DataError, DataRepository, Result};
 use crate::utils::save_to_json::save_to_json;
 use serde::Serialize;
 
 pub struct Json {
     pub path: String,
+    pub pretty: bool,
 }
 
 impl Json {
-    pub fn new(path: String) -> Self {
-        Self { path }
+    pub fn new(path: String, pretty: bool) -> Self {
+        Self { path, pretty }
     }
 }
 
 impl DataRepository for Json {
     async fn save<T: Serialize>(&self, data: &T) -> Result<()> {
-        save_to_json(&self.path, data).await;
-        Ok(())
+        save_to_json(&self.path, data, self.pretty)
+            .await
+            .map_err(|err| DataError::Save(self.path.clone(), err))
     }
 }
@@ -1,3 +1,4 @@
 pub mod postgres;
 pub mod statistic_generators;
 pub mod json;
+pub mod aggregators;
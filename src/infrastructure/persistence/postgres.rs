@@ -18,7 +18,7 @@ impl StorageRepository for Postgres {
         // Сохранение чата
         let chat_model = ChatModel {
             id: Set(data.id),
-            name: Set(data.name.clone()),
+            name: Set(data.name.clone().unwrap_or_default()),
             r#type: Set(data.r#type.clone()),
         };
 
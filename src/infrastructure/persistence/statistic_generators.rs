@@ -1,156 +1,48 @@
-use crate::domain::types::chat::{Chat, Message};
-use crate::domain::types::stats::{
-    AdditionalMessagesStats, AllStats, CallsStats, ChatStats, EmojiStats, MessagesStats,
-    MostUsedSticker, WordStats,
-};
-use crate::ingest::data_preparer::DataPreparer;
-use crate::infrastructure::repositories::statistic_repository::{
-    Result, StatisticError, StatisticRepository,
-};
-use chrono::NaiveDate;
-use regex::Regex;
+use crate::domain::types::chat::{Chat, ChatOwner};
+use crate::domain::types::options::StatsOptions;
+use crate::domain::types::stats::{AllStats, ExportStats};
+use crate::infrastructure::persistence::aggregators::AllStatsAggregator;
+use crate::infrastructure::repositories::aggregator::Aggregator;
+use crate::infrastructure::repositories::statistic_repository::{Result, StatisticRepository};
+use crate::ingest::message_stream::ChatHeader;
 
-impl StatisticRepository for ChatStats {
-    type Data<'b> = &'b Chat;
-
-    async fn get_stats(data: Self::Data<'_>) -> Result<Self> {
-        Ok(ChatStats {
-            messages_stats: MessagesStats::get_stats(data.messages.iter().collect()).await?,
-            additional_messages_stats: AdditionalMessagesStats::get_stats(&data.messages).await?,
-        })
-    }
-}
-
-impl StatisticRepository for MessagesStats {
-    type Data<'b> = Vec<&'b Message>;
-
-    async fn get_stats(data: Self::Data<'_>) -> Result<Self> {
-        Ok(Self {
-            first_message: DataPreparer::first_message(data.iter().copied())
-                .map(|message| message.clone().into()),
-            last_message: DataPreparer::last_message(data.iter().copied())
-                .map(|message| message.clone().into()),
-            total_messages_count: data.len(),
-            owner_messages_count: data
-                .iter()
-                .filter(|message| match &message.from_id {
-                    None => false,
-                    Some(id) => id == "user5769929151",
-                })
-                .count(),
-            member_messages_count: data
-                .iter()
-                .filter(|message| match &message.from_id {
-                    None => false,
-                    Some(id) => id != "user5769929151",
-                })
-                .count(),
-        })
-    }
-}
-
-impl StatisticRepository for AdditionalMessagesStats {
-    type Data<'b> = &'b Vec<Message>;
-
-    async fn get_stats(data: Self::Data<'_>) -> Result<Self> {
-        Ok(Self {
-            total_characters_count: DataPreparer::character_count(data.iter())
-                .map_err(StatisticError::FailedToGetData)?,
-            owner_characters_count: DataPreparer::character_count_filtered(
-                data.iter(),
-                |message| match &message.from_id {
-                    None => false,
-                    Some(id) => id == "user5769929151",
-                },
-            )
-            .map_err(StatisticError::FailedToGetData)?,
-            member_characters_count: DataPreparer::character_count_filtered(
-                data.iter(),
-                |message| match &message.from_id {
-                    None => false,
-                    Some(id) => id != "user5769929151",
-                },
-            )
-            .map_err(StatisticError::FailedToGetData)?,
-        })
-    }
-}
-
-impl StatisticRepository for CallsStats {
-    type Data<'a> = Vec<&'a Message>;
+impl StatisticRepository for AllStats {
+    type Data<'b> = (&'b Chat, StatsOptions, &'b str, &'b str);
 
     async fn get_stats(data: Self::Data<'_>) -> Result<Self> {
-        let duration = DataPreparer::calls_durations(data.iter().copied())
-            .map_err(StatisticError::FailedToGetData)?;
-
-        Ok(Self {
-            total_calls_durations_sec: duration,
-            total_calls_durations_min: duration / 60,
-            longest_call_durations_min: DataPreparer::longest_call(data.iter().copied()),
-        })
+        let (chat, options, source_dir, owner_id) = data;
+
+        let mut stats = AllStatsAggregator::new(
+            ChatHeader::from(chat),
+            options,
+            source_dir,
+            ChatOwner::Id(owner_id.to_string()),
+        )
+        .aggregate(&chat.messages);
+        if !stats.replies.is_resolved() {
+            for message in &chat.messages {
+                stats.replies.resolve(message);
+            }
+        }
+
+        Ok(stats)
     }
 }
 
-impl StatisticRepository for MostUsedSticker {
-    type Data<'b> = &'b Vec<Message>;
+impl StatisticRepository for ExportStats {
+    type Data<'b> = (&'b [Chat], StatsOptions, &'b str, &'b str);
 
     async fn get_stats(data: Self::Data<'_>) -> Result<Self> {
-        let owner =
-            DataPreparer::most_used_sticker(data.iter(), |message| match &message.from_id {
-                None => false,
-                Some(id) => id == "user5769929151",
-            });
-
-        let member =
-            DataPreparer::most_used_sticker(data.iter(), |message| match &message.from_id {
-                None => false,
-                Some(id) => id != "user5769929151",
-            });
+        let (chats, options, source_dir, owner_id) = data;
 
-        Ok(Self {
-            owner_most_used_sticker_count: owner.0,
-            owner_most_used_sticker: owner.1,
-            member_most_used_sticker_count: member.0,
-            member_most_used_sticker: member.1,
-        })
-    }
-}
-
-impl StatisticRepository for AllStats {
-    type Data<'b> = (&'b Chat, i32, &'b str);
-
-    async fn get_stats(data: Self::Data<'_>) -> Result<Self> {
-        let (chat, year, source_dir) = data;
-        let days_in_year = NaiveDate::from_ymd_opt(year, 12, 31)
-            .and_then(|end| {
-                NaiveDate::from_ymd_opt(year, 1, 1)
-                    .map(|start| (end.signed_duration_since(start).num_days() + 1) as f64)
-            })
-            .unwrap_or(365.0);
+        let mut stats = Vec::with_capacity(chats.len());
+        for chat in chats {
+            stats.push(AllStats::get_stats((chat, options, source_dir, owner_id)).await?);
+        }
 
         Ok(Self {
-            year,
-            source_dir: source_dir.to_string(),
-            chat_stats: ChatStats::get_stats(chat).await?,
-            occurrences: MessagesStats::get_stats(chat.occurrences(
-                &Regex::new(r"(?i)\bлюблю\b.*\bтебя\b|\bтебя\b.*\bлюблю\b|\bи я тебя\b").unwrap(),
-            ))
-            .await?,
-            longest_conversation: MessagesStats::get_stats(chat.longest_conversation()).await?,
-            calls_stats: CallsStats::get_stats(chat.calls()).await?,
-            most_used_sticker: MostUsedSticker::get_stats(&chat.messages).await?,
-            emoji_stats: {
-                let (emoji, count) = DataPreparer::top_emoji(chat.messages.iter());
-                EmojiStats {
-                    top_emoji: emoji,
-                    top_emoji_count: count,
-                }
-            },
-            word_stats: WordStats {
-                top_words: DataPreparer::top_words(chat.messages.iter(), 5),
-            },
-            avg_messages_per_day: chat.messages.len() as f64 / days_in_year,
-            streak: DataPreparer::message_streak(chat.messages.iter()),
+            owner_id: owner_id.to_string(),
+            chats: stats,
         })
     }
 }
@@ -0,0 +1,27 @@
+use crate::domain::types::chat::Message;
+
+/// Инкрементальный сборщик статистики: получает сообщения по одному
+/// в хронологическом порядке и не хранит их.
+///
+/// Составные сборщики (например, `AllStatsAggregator`) передают каждое
+/// сообщение всем вложенным, поэтому вся статистика считается за один проход
+/// и одинаково работает как для чата в памяти, так и при потоковом разборе.
+pub trait Aggregator {
+    type Output;
+
+    fn observe(&mut self, message: &Message);
+
+    fn finish(self) -> Self::Output;
+
+    /// Прогоняет сообщения через сборщик и возвращает результат.
+    fn aggregate<'a, I>(mut self, messages: I) -> Self::Output
+    where
+        Self: Sized,
+        I: IntoIterator<Item = &'a Message>,
+    {
+        for message in messages {
+            self.observe(message);
+        }
+        self.finish()
+    }
+}
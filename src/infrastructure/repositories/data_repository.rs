@@ -8,7 +8,6 @@ pub type Result<T> = std::result::Result<T, DataError>;
 
 #[derive(Debug, thiserror::Error)]
 pub enum DataError {
-    #[error("save error")]
-    #[allow(dead_code)]
-    Save,
+    #[error("Failed to save `{0}`")]
+    Save(String, #[source] std::io::Error),
 }
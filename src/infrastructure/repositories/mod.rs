@@ -1,3 +1,4 @@
+pub mod aggregator;
 pub mod storage_repository;
 pub mod statistic_repository;
 pub mod data_repository;
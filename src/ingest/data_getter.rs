@@ -1,7 +1,14 @@
-use serde::de::DeserializeOwned;
+use crate::domain::types::chat::Chat;
+use crate::domain::types::export::ExportFile;
+use crate::ingest::export_archive::ExportArchive;
+use crate::ingest::html_parser::HtmlParser;
+use crate::ingest::message_stream::{ChatHeader, ChatSink, ExportSeed};
+use serde::de::{DeserializeOwned, DeserializeSeed};
+use serde::Serialize;
 use std::fs;
 use std::fs::File;
-use std::io::BufReader;
+use std::io::{BufReader, Read};
+use std::path::Path;
 
 pub struct DataGetter;
 
@@ -12,23 +19,134 @@ impl DataGetter {
     pub async fn process_file_from_str<T: DeserializeOwned>(
         file_path: &str,
     ) -> Result<T> {
-        let file_content = fs::read_to_string(file_path).map_err(DataGetterError::ReadFile)?;
-        serde_json::from_str(&file_content).map_err(DataGetterError::ParseData)
+        let file_content = fs::read_to_string(file_path)
+            .map_err(|err| DataGetterError::ReadFile(file_path.to_string(), err))?;
+        serde_json::from_str(&file_content)
+            .map_err(|err| DataGetterError::ParseData(file_path.to_string(), err))
     }
 
     pub async fn process_file_from_reader<T: DeserializeOwned>(
         file_path: &str,
     ) -> Result<T> {
-        let file = File::open(file_path).map_err(DataGetterError::ReadFile)?;
+        if ExportArchive::is_archive(file_path) {
+            let mut archive = Self::open_archive(file_path)?;
+            let entry = archive
+                .result_json()
+                .map_err(|err| DataGetterError::ReadArchive(file_path.to_string(), err))?;
+            return serde_json::from_reader(BufReader::new(entry))
+                .map_err(|err| DataGetterError::ParseData(file_path.to_string(), err));
+        }
+
+        let file = Self::open_file(file_path)?;
         let reader = BufReader::new(file);
-        serde_json::from_reader(reader).map_err(DataGetterError::ParseData)
+        serde_json::from_reader(reader)
+            .map_err(|err| DataGetterError::ParseData(file_path.to_string(), err))
+    }
+
+    pub fn is_html_export(file_path: &str) -> bool {
+        let path = Path::new(file_path);
+        path.extension().is_some_and(|extension| extension == "html")
+            || path.join("messages.html").is_file()
+    }
+
+    pub async fn process_html_export(file_path: &str) -> Result<Chat> {
+        let pages = HtmlParser::pages(Path::new(file_path))
+            .map_err(|err| DataGetterError::ReadFile(file_path.to_string(), err))?;
+        if pages.is_empty() {
+            return Err(DataGetterError::NoHtmlPages(file_path.to_string()));
+        }
+
+        let mut parser = HtmlParser::new();
+        for page in pages {
+            let html = fs::read_to_string(&page)
+                .map_err(|err| DataGetterError::ReadFile(page.display().to_string(), err))?;
+            parser.parse_page(&html);
+        }
+
+        Ok(parser.finish())
+    }
+
+    /// Загружает экспорт целиком: `result.json`, zip-архив или HTML.
+    pub async fn load_export(file_path: &str) -> Result<ExportFile> {
+        if Self::is_html_export(file_path) {
+            return Ok(ExportFile::Chat(Self::process_html_export(file_path).await?));
+        }
+
+        Self::process_file_from_reader(file_path).await
+    }
+
+    /// Разбирает экспорт потоком, передавая сообщения в `sink` по одному.
+    /// HTML-экспорт разбирается в память и затем передаётся в `sink` целиком.
+    pub async fn stream_file<S: ChatSink>(file_path: &str, sink: &mut S) -> Result<()> {
+        if Self::is_html_export(file_path) {
+            let chat = Self::process_html_export(file_path).await?;
+            return Self::feed_chat(chat, sink).map_err(DataGetterError::Sink);
+        }
+
+        if ExportArchive::is_archive(file_path) {
+            let mut archive = Self::open_archive(file_path)?;
+            let entry = archive
+                .result_json()
+                .map_err(|err| DataGetterError::ReadArchive(file_path.to_string(), err))?;
+            return Self::stream_reader(file_path, BufReader::new(entry), sink);
+        }
+
+        let file = Self::open_file(file_path)?;
+        Self::stream_reader(file_path, BufReader::new(file), sink)
+    }
+
+    fn open_file(file_path: &str) -> Result<File> {
+        File::open(file_path).map_err(|err| DataGetterError::ReadFile(file_path.to_string(), err))
+    }
+
+    fn open_archive(file_path: &str) -> Result<ExportArchive> {
+        ExportArchive::open(file_path)
+            .map_err(|err| DataGetterError::ReadArchive(file_path.to_string(), err))
+    }
+
+    fn feed_chat<S: ChatSink>(chat: Chat, sink: &mut S) -> anyhow::Result<()> {
+        sink.start_chat(ChatHeader::from(&chat))?;
+        for message in chat.messages {
+            sink.message(message)?;
+        }
+        sink.end_chat()
+    }
+
+    fn stream_reader<R: Read, S: ChatSink>(file_path: &str, reader: R, sink: &mut S) -> Result<()> {
+        let parse_error = |err| DataGetterError::ParseData(file_path.to_string(), err);
+        let mut deserializer = serde_json::Deserializer::from_reader(reader);
+        ExportSeed { sink }
+            .deserialize(&mut deserializer)
+            .map_err(parse_error)?;
+        deserializer.end().map_err(parse_error)
+    }
+
+    /// Для zip-экспорта распаковывает медиафайлы, упомянутые в статистике.
+    pub async fn extract_media<T: Serialize>(
+        file_path: &str,
+        stats: &T,
+        target_dir: &str,
+    ) -> Result<usize> {
+        if !ExportArchive::is_archive(file_path) {
+            return Ok(0);
+        }
+
+        ExportArchive::open(file_path)
+            .and_then(|mut archive| archive.extract_media(stats, Path::new(target_dir)))
+            .map_err(|err| DataGetterError::ReadArchive(file_path.to_string(), err))
     }
 }
 
 #[derive(Debug, thiserror::Error)]
 pub enum DataGetterError{
-    #[error("Failed to read file: 0")]
-    ReadFile(#[source] std::io::Error),
-    #[error("Failed to parse data: 0")]
-    ParseData(#[source] serde_json::Error),
+    #[error("Failed to read file `{0}`")]
+    ReadFile(String, #[source] std::io::Error),
+    #[error("Failed to parse `{0}`")]
+    ParseData(String, #[source] serde_json::Error),
+    #[error("Failed to read archive `{0}`")]
+    ReadArchive(String, #[source] zip::result::ZipError),
+    #[error("No messages*.html pages found in `{0}`")]
+    NoHtmlPages(String),
+    #[error("{0:#}")]
+    Sink(anyhow::Error),
 }
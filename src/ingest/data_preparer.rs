@@ -1,14 +1,122 @@
-use crate::domain::types::chat::{Chat, Message, MessageText, TextEntity};
-use crate::domain::types::stats::{MinimalMessage, Streak, WordCount};
-use chrono::{Duration, TimeDelta, Utc};
+use crate::domain::types::chat::{Chat, Message, MessageText, Reaction, TextEntity};
+use crate::domain::types::date_range::DateRange;
 use regex::Regex;
-
-type Result<T> = core::result::Result<T, DataPreparerError>;
+use std::sync::LazyLock;
+use unicode_properties::emoji::{self, EmojiStatus, UnicodeEmoji};
+use unicode_segmentation::UnicodeSegmentation;
+
+static WORD_RE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"[\p{L}\p{N}]+").unwrap());
+
+pub static OCCURRENCES_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?i)\bлюблю\b.*\bтебя\b|\bтебя\b.*\bлюблю\b|\bи я тебя\b").unwrap()
+});
+
+const STOP_WORDS: &[&str] = &[
+    "а",
+    "да",
+    "же",
+    "за",
+    "и",
+    "из",
+    "или",
+    "к",
+    "как",
+    "на",
+    "не",
+    "ну",
+    "о",
+    "по",
+    "про",
+    "с",
+    "со",
+    "то",
+    "у",
+    "я",
+    "мы",
+    "ты",
+    "вы",
+    "он",
+    "она",
+    "оно",
+    "они",
+    "мне",
+    "меня",
+    "мной",
+    "тебя",
+    "тебе",
+    "тобой",
+    "нас",
+    "нам",
+    "вас",
+    "вам",
+    "его",
+    "ее",
+    "их",
+    "мой",
+    "моя",
+    "мои",
+    "твой",
+    "твоя",
+    "твои",
+    "наш",
+    "наша",
+    "наши",
+    "ваш",
+    "ваша",
+    "ваши",
+    "это",
+    "эта",
+    "эти",
+    "этот",
+    "тот",
+    "та",
+    "те",
+    "там",
+    "тут",
+    "здесь",
+    "вот",
+    "ли",
+    "бы",
+    "быть",
+    "есть",
+    "были",
+    "был",
+    "была",
+    "будет",
+    "буду",
+    "будешь",
+    "будем",
+    "будете",
+    "еще",
+    "ещё",
+    "уже",
+    "если",
+    "чтобы",
+    "что",
+    "кто",
+    "когда",
+    "где",
+    "почему",
+    "потом",
+    "тогда",
+    "сейчас",
+    "сегодня",
+    "вчера",
+    "завтра",
+    "очень",
+    "просто",
+    "вообще",
+    "ладно",
+    "ага",
+    "в",
+    "тяк",
+    "так",
+];
 
 pub struct DataPreparer;
 
 impl DataPreparer {
-    fn message_text_to_string(text: &MessageText) -> String {
+    pub fn message_text_to_string(text: &MessageText) -> String {
         match text {
             MessageText::Plain(text) => text.clone(),
             MessageText::Entities(entities) => {
@@ -24,431 +132,191 @@ impl DataPreparer {
         }
     }
 
-    fn is_emoji_char(ch: char) -> bool {
-        matches!(
-            ch as u32,
-            0x1F1E6..=0x1F1FF
-                | 0x1F300..=0x1F5FF
-                | 0x1F600..=0x1F64F
-                | 0x1F680..=0x1F6FF
-                | 0x1F700..=0x1F77F
-                | 0x1F780..=0x1F7FF
-                | 0x1F800..=0x1F8FF
-                | 0x1F900..=0x1F9FF
-                | 0x1FA00..=0x1FAFF
-                | 0x2600..=0x26FF
-                | 0x2700..=0x27BF
-        )
+    pub fn text_matches(text: &MessageText, search: &Regex) -> bool {
+        match text {
+            MessageText::Plain(text) => search.is_match(text),
+            MessageText::Entities(vec) => vec.iter().any(|item| match item {
+                TextEntity::Text(text) => search.is_match(text),
+                TextEntity::Entity(entity) => search.is_match(&entity.text),
+            }),
+        }
     }
 
-    fn extract_emojis(text: &str) -> Vec<String> {
-        let mut emojis = Vec::new();
-        let mut iter = text.chars().peekable();
-
-        while let Some(ch) = iter.next() {
-            if Self::is_emoji_char(ch) {
-                let mut emoji = ch.to_string();
-                if let Some('\u{FE0F}') = iter.peek() {
-                    emoji.push(iter.next().unwrap());
-                }
-                emojis.push(emoji);
-            }
-        }
+    /// Эмодзи текста по графемам, в полной (fully-qualified) форме: ZWJ-
+    /// последовательности, оттенки кожи, флаги и клавиши считаются одним эмодзи.
+    /// Символы с текстовым представлением по умолчанию (`©`, `™`, `↔`) без
+    /// `U+FE0F` эмодзи не считаются.
+    pub fn extract_emojis(text: &str) -> Vec<String> {
+        text.graphemes(true)
+            .filter(|grapheme| Self::is_emoji_presentation(grapheme))
+            .filter_map(emojis::get)
+            .map(|emoji| emoji.as_str().to_string())
+            .collect()
+    }
 
-        emojis
+    fn is_emoji_presentation(grapheme: &str) -> bool {
+        let default_presentation = grapheme.chars().next().is_some_and(|c| {
+            matches!(
+                c.emoji_status(),
+                EmojiStatus::EmojiPresentation
+                    | EmojiStatus::EmojiPresentationAndModifierBase
+                    | EmojiStatus::EmojiPresentationAndEmojiComponent
+                    | EmojiStatus::EmojiPresentationAndModifierAndEmojiComponent
+            )
+        });
+        default_presentation
+            || grapheme.chars().any(|c| {
+                emoji::is_emoji_presentation_selector(c)
+                    || emoji::is_zwj(c)
+                    || matches!(c, '\u{1F3FB}'..='\u{1F3FF}')
+            })
     }
 
-    pub fn top_emoji<'a, I>(messages: I) -> (Option<String>, i32)
-    where
-        I: Iterator<Item = &'a Message>,
-    {
-        let mut counts: std::collections::HashMap<String, i32> = std::collections::HashMap::new();
+    pub fn extract_words(text: &str) -> impl Iterator<Item = String> + '_ {
+        WORD_RE
+            .find_iter(text)
+            .map(|word| word.as_str().to_lowercase())
+            .filter(|word| word.len() >= 2 && !STOP_WORDS.contains(&word.as_str()))
+    }
 
-        for message in messages {
-            let text = Self::message_text_to_string(&message.text);
-            for emoji in Self::extract_emojis(&text) {
-                *counts.entry(emoji).or_insert(0) += 1;
-            }
+    pub fn message_characters(message: &Message) -> usize {
+        match &message.text {
+            MessageText::Plain(text) => text.len(),
+            MessageText::Entities(entities) => entities
+                .iter()
+                .map(|entity| match entity {
+                    TextEntity::Text(text) => text.len(),
+                    TextEntity::Entity(entity) => entity.text.len(),
+                })
+                .sum(),
         }
-
-        counts
-            .into_iter()
-            .max_by(|a, b| a.1.cmp(&b.1))
-            .map(|(emoji, count)| (Some(emoji), count))
-            .unwrap_or((None, 0))
     }
 
-    pub fn top_words<'a, I>(messages: I, limit: usize) -> Vec<WordCount>
-    where
-        I: Iterator<Item = &'a Message>,
-    {
-        let word_re = Regex::new(r"[\p{L}\p{N}]+").unwrap();
-        let stop_words: std::collections::HashSet<&'static str> = [
-            "а",
-            "да",
-            "же",
-            "за",
-            "и",
-            "из",
-            "или",
-            "к",
-            "как",
-            "на",
-            "не",
-            "ну",
-            "о",
-            "по",
-            "про",
-            "с",
-            "со",
-            "то",
-            "у",
-            "я",
-            "мы",
-            "ты",
-            "вы",
-            "он",
-            "она",
-            "оно",
-            "они",
-            "мне",
-            "меня",
-            "мной",
-            "тебя",
-            "тебе",
-            "тобой",
-            "нас",
-            "нам",
-            "вас",
-            "вам",
-            "его",
-            "ее",
-            "их",
-            "мой",
-            "моя",
-            "мои",
-            "твой",
-            "твоя",
-            "твои",
-            "наш",
-            "наша",
-            "наши",
-            "ваш",
-            "ваша",
-            "ваши",
-            "это",
-            "эта",
-            "эти",
-            "этот",
-            "тот",
-            "та",
-            "те",
-            "там",
-            "тут",
-            "здесь",
-            "вот",
-            "ли",
-            "бы",
-            "быть",
-            "есть",
-            "были",
-            "был",
-            "была",
-            "будет",
-            "буду",
-            "будешь",
-            "будем",
-            "будете",
-            "еще",
-            "ещё",
-            "уже",
-            "если",
-            "чтобы",
-            "что",
-            "кто",
-            "когда",
-            "где",
-            "почему",
-            "потом",
-            "тогда",
-            "сейчас",
-            "сегодня",
-            "вчера",
-            "завтра",
-            "очень",
-            "просто",
-            "вообще",
-            "ладно",
-            "ага",
-            "в",
-            "тяк",
-            "так",
-        ]
-        .into_iter()
-        .collect();
-        let mut counts: std::collections::HashMap<String, i32> = std::collections::HashMap::new();
-
-        for message in messages {
-            let text = Self::message_text_to_string(&message.text);
-            for word in word_re.find_iter(&text) {
-                let value = word.as_str().to_lowercase();
-                if value.len() < 2 || stop_words.contains(value.as_str()) {
-                    continue;
-                }
-                *counts.entry(value).or_insert(0) += 1;
-            }
+    /// Ключ стикера: путь к файлу, а если файл не выгружен, то его имя.
+    pub fn sticker_key(message: &Message) -> Option<String> {
+        if message.media_type.as_deref() != Some("sticker") {
+            return None;
         }
 
-        let mut items: Vec<WordCount> = counts
-            .into_iter()
-            .map(|(word, count)| WordCount { word, count })
-            .collect();
-        items.sort_by(|a, b| b.count.cmp(&a.count).then_with(|| a.word.cmp(&b.word)));
-        items.truncate(limit);
-        items
-    }
-    pub fn first_message<'a, I>(messages: I) -> Option<&'a Message>
-    where
-        I: Iterator<Item = &'a Message>,
-    {
-        match messages.min_by(|x, y| x.date.cmp(&y.date)) {
-            None => None,
-            Some(message) => Some(message),
+        match &message.file {
+            Some(path) if !path.starts_with("(File not included") => Some(path.clone()),
+            _ => message.file_name.clone(),
         }
     }
 
-    pub fn last_message<'a, I>(messages: I) -> Option<&'a Message>
-    where
-        I: Iterator<Item = &'a Message>,
-    {
-        match messages.max_by(|x, y| x.date.cmp(&y.date)) {
-            None => None,
-            Some(message) => Some(message),
+    /// Ключ реакции: эмодзи в полной форме или `custom_emoji:<document_id>`.
+    pub fn reaction_key(reaction: &Reaction) -> Option<String> {
+        match (&reaction.emoji, &reaction.document_id) {
+            (Some(emoji), _) => Some(
+                emojis::get(emoji)
+                    .map_or(emoji.as_str(), |emoji| emoji.as_str())
+                    .to_string(),
+            ),
+            (None, Some(document_id)) => Some(format!("{}:{document_id}", reaction.r#type)),
+            (None, None) => None,
         }
     }
 
-    pub fn character_count<'a, I>(messages: I) -> Result<usize>
-    where
-        I: Iterator<Item = &'a Message>,
-    {
-        let mut total_characters = 0;
+    /// Домен ссылки без `www.`, порта и пути: `https://www.Example.com:8080/a`
+    /// даёт `example.com`.
+    pub fn link_domain(link: &str) -> Option<String> {
+        let rest = link.split_once("://").map_or(link, |(_, rest)| rest);
+        let host = rest.split(['/', '?', '#']).next()?;
+        let host = host.rsplit_once('@').map_or(host, |(_, host)| host);
+        let host = host.split(':').next()?.trim_start_matches("www.");
 
-        for message in messages {
-            match &message.text {
-                MessageText::Plain(text) => {
-                    total_characters += text.len();
-                }
-                MessageText::Entities(entities) => {
-                    for entity in entities {
-                        match entity {
-                            TextEntity::Text(text) => {
-                                total_characters += text.len();
-                            }
-                            TextEntity::Entity(entity) => {
-                                total_characters += entity.text.len();
-                            }
-                        }
-                    }
-                }
-            }
-        }
-
-        Ok(total_characters)
-    }
-
-    pub fn character_count_filtered<'a, I, F>(messages: I, mut filter: F) -> Result<usize>
-    where
-        I: Iterator<Item = &'a Message>,
-        F: FnMut(&Message) -> bool,
-    {
-        Self::character_count(messages.filter(|message| filter(message)))
+        (!host.is_empty() && host.contains('.')).then(|| host.to_lowercase())
     }
 
-    pub fn calls_durations<'a, I>(messages: I) -> Result<u32>
-    where
-        I: Iterator<Item = &'a Message>,
-    {
-        let mut duration = 0;
-
-        for message in messages {
-            if let Some(dur) = message.duration_seconds {
-                duration += dur
-            }
+    /// Тип медиа сообщения. У фото и документов экспорт не пишет
+    /// `media_type`, они определяются по полям `photo` и `file`.
+    pub fn media_type(message: &Message) -> Option<&str> {
+        match (&message.media_type, &message.photo, &message.file) {
+            (Some(media_type), _, _) => Some(media_type),
+            (None, Some(_), _) => Some("photo"),
+            (None, None, Some(_)) => Some("document"),
+            (None, None, None) => None,
         }
-        Ok(duration as u32)
     }
+}
 
-    pub fn longest_call<'a, I>(messages: I) -> Option<MinimalMessage>
-    where
-        I: Iterator<Item = &'a Message>,
-    {
-        let mut max_duration_message = None;
-        let mut max_duration = 0;
-
-        for message in messages {
-            if let Some(dur) = message.duration_seconds {
-                if dur > max_duration {
-                    max_duration_message = Some(message);
-                    max_duration = dur;
-                }
-            }
-        }
-        max_duration_message.map(|m| m.clone().into())
+impl Chat {
+    pub async fn retain_by_date(&mut self, range: &DateRange) {
+        self.messages.retain(|message| range.contains(message.date));
+        self.messages.sort_by_key(|message| message.date);
     }
 
-    pub fn most_used_sticker<'a, I, F>(messages: I, mut filter: F) -> (i32, Option<MinimalMessage>)
-    where
-        I: Iterator<Item = &'a Message>,
-        F: FnMut(&Message) -> bool,
-    {
-        let mut max_message = None;
-        let mut max_used = 0;
-        let mut usage_counter = std::collections::HashMap::new();
+    /// Угадывает `from_id` владельца экспорта. В личном чате `id` чата совпадает
+    /// с id собеседника, поэтому владельцем считается самый частый отправитель,
+    /// не являющийся собеседником. В остальных чатах владелец не угадывается.
+    pub fn detect_owner(&self) -> Option<String> {
+        const PERSONAL_CHAT: &str = "personal_chat";
 
-        for message in messages {
-            if message.media_type == Some("sticker".to_string()) && filter(message) {
-                let key = match &message.file {
-                    Some(path) if !path.starts_with("(File not included") => Some(path.clone()),
-                    _ => message.file_name.clone(),
-                };
-
-                if let Some(path) = key {
-                    let count = usage_counter.entry(path).or_insert(0);
-                    *count += 1;
-                    if *count > max_used {
-                        max_used = *count;
-                        max_message = Some(message.clone().into());
-                    }
-                }
-            }
+        if self.r#type != PERSONAL_CHAT {
+            return None;
         }
 
-        (max_used, max_message)
-    }
-
-    pub fn message_streak<'a, I>(messages: I) -> Streak
-    where
-        I: Iterator<Item = &'a Message>,
-    {
-        let mut max_streak = Streak {
-            count: 0,
-            start: Utc::now().date_naive(),
-            end: Utc::now().date_naive(),
-        };
+        let peer_id = format!("user{}", self.id);
+        let mut counts: std::collections::HashMap<&str, usize> = std::collections::HashMap::new();
 
-        let mut current_streak = Streak {
-            count: 0,
-            start: Utc::now().date_naive(),
-            end: Utc::now().date_naive(),
-        };
-
-        let mut previous_date = None;
-
-        for message in messages {
-            let message_date = message.date.date_naive();
-
-            match previous_date {
-                None => {
-                    // Инициализируем начальную дату
-                    current_streak.start = message_date;
-                    current_streak.end = message_date;
-                    current_streak.count = 1;
-                }
-                Some(prev) if message_date == prev + Duration::days(1) => {
-                    // Продолжаем последовательность
-                    current_streak.end = message_date;
-                    current_streak.count += 1;
-                }
-                Some(prev) if message_date > prev + Duration::days(1) => {
-                    // Последовательность прервалась
-                    if current_streak.count > max_streak.count {
-                        max_streak = current_streak;
-                    }
-                    current_streak = Streak {
-                        count: 1,
-                        start: message_date,
-                        end: message_date,
-                    };
+        for message in &self.messages {
+            if let Some(id) = &message.from_id {
+                if *id == peer_id {
+                    continue;
                 }
-                _ => {}
+                *counts.entry(id.as_str()).or_insert(0) += 1;
             }
-
-            previous_date = Some(message_date);
         }
 
-        // Проверяем последнюю последовательность
-        if current_streak.count > max_streak.count {
-            max_streak = current_streak;
-        }
-
-        max_streak
+        counts
+            .into_iter()
+            .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(a.0)))
+            .map(|(id, _)| id.to_string())
     }
 }
 
-impl Chat {
-    pub async fn retain_by_date(
-        &mut self,
-        start: chrono::DateTime<chrono::Utc>,
-        end: chrono::DateTime<chrono::Utc>,
-    ) {
-        self.messages
-            .retain(|message| message.date >= start && message.date <= end);
-        self.messages.sort_by(|a, b| a.date.cmp(&b.date));
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn chat(r#type: &str, senders: &[&str]) -> Chat {
+        Chat {
+            id: 222,
+            name: None,
+            r#type: r#type.to_string(),
+            messages: senders
+                .iter()
+                .map(|from_id| Message {
+                    from_id: Some(from_id.to_string()),
+                    ..Default::default()
+                })
+                .collect(),
+        }
     }
 
-    pub fn occurrences(&self, search: &Regex) -> Vec<&Message> {
-        self.messages
-            .iter()
-            .filter(|message| match &message.text {
-                MessageText::Plain(text) => search.is_match(text),
-                MessageText::Entities(vec) => vec.iter().any(|item| match item {
-                    TextEntity::Text(text) => search.is_match(text),
-                    TextEntity::Entity(entity) => search.is_match(&entity.text),
-                }),
-            })
-            .collect()
+    #[test]
+    fn detect_owner_skips_peer_in_personal_chat() {
+        let chat = chat("personal_chat", &["user222", "user222", "user111"]);
+        assert_eq!(chat.detect_owner().as_deref(), Some("user111"));
     }
 
-    pub fn calls(&self) -> Vec<&Message> {
-        const CALL_ACTION: &str = "phone_call";
-
-        self.messages
-            .iter()
-            .filter(|message| match &message.action {
-                None => false,
-                Some(action) => action == CALL_ACTION,
-            })
-            .collect()
+    #[test]
+    fn detect_owner_ignores_group_chats() {
+        let chat = chat("private_group", &["user1", "user1", "user2"]);
+        assert_eq!(chat.detect_owner(), None);
     }
 
-    pub fn longest_conversation(&self) -> Vec<&Message> {
-        let mut longest_conversation = vec![];
-        let mut conversation = vec![];
-        for message in self.messages.iter() {
-            if conversation.is_empty() {
-                conversation.push(message);
-            } else {
-                let time = message.date - conversation.last().unwrap().date;
-                if time < TimeDelta::minutes(15) {
-                    conversation.push(message);
-                } else if conversation.len() > longest_conversation.len() {
-                    longest_conversation = conversation.clone();
-                } else {
-                    conversation.clear();
-                    conversation.push(message);
-                }
-            }
-        }
-        longest_conversation
+    #[test]
+    fn extract_emojis_skips_text_presentation_symbols() {
+        assert!(DataPreparer::extract_emojis("© 2024 Acme™, a ↔ b").is_empty());
     }
-}
 
-#[derive(Debug, thiserror::Error)]
-pub enum DataPreparerError {
-    #[error("No data to prepare")]
-    #[allow(dead_code)]
-    NoData,
-    #[error("Invalid calls durations in message: {id}")]
-    #[allow(dead_code)]
-    InvalidCallsArray { id: i64 },
+    #[test]
+    fn extract_emojis_keeps_emoji_sequences() {
+        let text = "ok 😀 ❤️ ©️ 👍🏽 👨‍💻 🇩🇪 ↔";
+        assert_eq!(
+            DataPreparer::extract_emojis(text),
+            ["😀", "❤️", "©️", "👍🏽", "👨‍💻", "🇩🇪"]
+        );
+    }
 }
@@ -0,0 +1,107 @@
+use serde::Serialize;
+use serde_json::Value;
+use std::fs::File;
+use std::io::BufReader;
+use std::path::Path;
+use zip::read::ZipFile;
+use zip::result::ZipError;
+use zip::ZipArchive;
+
+const RESULT_FILE: &str = "result.json";
+
+/// Экспорт Telegram, упакованный в zip. `result.json` читается прямо из
+/// архива, а пути медиафайлов разрешаются относительно его каталога.
+pub struct ExportArchive {
+    archive: ZipArchive<BufReader<File>>,
+    root: String,
+}
+
+impl ExportArchive {
+    pub fn is_archive(file_path: &str) -> bool {
+        Path::new(file_path)
+            .extension()
+            .is_some_and(|extension| extension.eq_ignore_ascii_case("zip"))
+    }
+
+    pub fn open(file_path: &str) -> Result<Self, ZipError> {
+        let archive = ZipArchive::new(BufReader::new(File::open(file_path)?))?;
+
+        let result = archive
+            .file_names()
+            .filter(|name| *name == RESULT_FILE || name.ends_with(&format!("/{RESULT_FILE}")))
+            .min_by_key(|name| name.len())
+            .ok_or(ZipError::FileNotFound)?;
+        let root = result[..result.len() - RESULT_FILE.len()].to_string();
+
+        Ok(Self { archive, root })
+    }
+
+    pub fn result_json(&mut self) -> Result<ZipFile<'_, BufReader<File>>, ZipError> {
+        self.archive.by_name(&format!("{}{RESULT_FILE}", self.root))
+    }
+
+    /// Распаковывает в `target_dir` только медиафайлы (`file`, `photo`,
+    /// `thumbnail`), на которые ссылается статистика. Возвращает их число.
+    pub fn extract_media<T: Serialize>(
+        &mut self,
+        stats: &T,
+        target_dir: &Path,
+    ) -> Result<usize, ZipError> {
+        let value = serde_json::to_value(stats).map_err(std::io::Error::other)?;
+        let mut paths = vec![];
+        Self::collect_media_paths(&value, &mut paths);
+        paths.sort();
+        paths.dedup();
+
+        let mut extracted = 0;
+        for path in paths {
+            let mut entry = match self.archive.by_name(&format!("{}{path}", self.root)) {
+                Ok(entry) => entry,
+                Err(ZipError::FileNotFound) => continue,
+                Err(err) => return Err(err),
+            };
+            let Some(relative) = entry.enclosed_name() else {
+                continue;
+            };
+            let target = target_dir.join(
+                relative
+                    .strip_prefix(&self.root)
+                    .unwrap_or(relative.as_path()),
+            );
+
+            if let Some(parent) = target.parent() {
+                std::fs::create_dir_all(parent)?;
+            }
+            std::io::copy(&mut entry, &mut File::create(target)?)?;
+            extracted += 1;
+        }
+
+        Ok(extracted)
+    }
+
+    fn collect_media_paths(value: &Value, paths: &mut Vec<String>) {
+        const MEDIA_KEYS: [&str; 3] = ["file", "photo", "thumbnail"];
+
+        match value {
+            Value::Object(map) => {
+                for (key, value) in map {
+                    match value {
+                        Value::String(path)
+                            if MEDIA_KEYS.contains(&key.as_str())
+                                && !path.starts_with("(File not included") =>
+                        {
+                            paths.push(path.clone())
+                        }
+                        _ => Self::collect_media_paths(value, paths),
+                    }
+                }
+            }
+            Value::Array(items) => {
+                for item in items {
+                    Self::collect_media_paths(item, paths);
+                }
+            }
+            _ => {}
+        }
+    }
+}
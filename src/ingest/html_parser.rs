@@ -0,0 +1,571 @@
+use crate::domain::types::chat::{
+    Chat, Message, MessageEntity, MessageText, Poll, PollAnswer, TextEntity,
+};
+use crate::utils::deserialize_datetime::LOCAL_DATE_FORMAT;
+use chrono::{DateTime, FixedOffset, NaiveDateTime, Utc};
+use regex::Regex;
+use scraper::{ElementRef, Html, Node, Selector};
+use std::path::{Path, PathBuf};
+use std::sync::LazyLock;
+
+static DURATION_RE: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"(?:(\d+) h(?:ours?)?)?\s*(?:(\d+) min)?\s*(?:(\d+) sec)?").unwrap()
+});
+static CLOCK_RE: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^(?:(\d+):)?(\d{1,2}):(\d{2})").unwrap());
+
+static HEADER: LazyLock<Selector> =
+    LazyLock::new(|| Selector::parse(".page_header .text.bold").unwrap());
+static MESSAGES: LazyLock<Selector> =
+    LazyLock::new(|| Selector::parse(".history > .message").unwrap());
+static LINK: LazyLock<Selector> = LazyLock::new(|| Selector::parse("a").unwrap());
+static TITLE: LazyLock<Selector> = LazyLock::new(|| Selector::parse(".title").unwrap());
+static STATUS: LazyLock<Selector> = LazyLock::new(|| Selector::parse(".status").unwrap());
+static QUESTION: LazyLock<Selector> = LazyLock::new(|| Selector::parse(".question").unwrap());
+static ANSWER: LazyLock<Selector> = LazyLock::new(|| Selector::parse(".answer").unwrap());
+
+/// Разбор HTML-экспорта Telegram Desktop (`messages.html`, `messages2.html`, ...)
+/// в ту же модель [`Chat`], что и у JSON-экспорта.
+///
+/// В HTML нет идентификаторов пользователей, поэтому `from_id` заполняется
+/// именем отправителя, а момент отправки считается по смещению из подсказки даты.
+///
+/// Идентификатора чата в HTML тоже нет, `id` чата всегда `0`. Статистика
+/// такого экспорта не совпадает по `chat_id` и ключам участников с JSON-
+/// экспортом того же чата, а владелец не угадывается и задаётся через
+/// `--owner` именем отправителя.
+///
+/// Служебные сообщения без подсказки даты (например, создание группы в начале
+/// чата) получают дату следующего сообщения с датой.
+pub struct HtmlParser {
+    chat: Chat,
+    last_from: Option<String>,
+    last_date: Option<(DateTime<Utc>, String)>,
+    /// Число сообщений в начале чата, для которых дата ещё не известна.
+    undated: usize,
+}
+
+impl HtmlParser {
+    /// Страницы экспорта в порядке номеров. `path` — каталог экспорта
+    /// или любой из файлов `messages*.html`.
+    pub fn pages(path: &Path) -> std::io::Result<Vec<PathBuf>> {
+        let dir = if path.is_dir() {
+            path
+        } else {
+            path.parent().unwrap_or(Path::new("."))
+        };
+
+        let mut pages: Vec<(u32, PathBuf)> = std::fs::read_dir(dir)?
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let name = entry.file_name().into_string().ok()?;
+                let number = name.strip_prefix("messages")?.strip_suffix(".html")?;
+                let number = if number.is_empty() {
+                    1
+                } else {
+                    number.parse().ok()?
+                };
+                Some((number, entry.path()))
+            })
+            .collect();
+        pages.sort_by_key(|(number, _)| *number);
+
+        Ok(pages.into_iter().map(|(_, page)| page).collect())
+    }
+
+    pub fn new() -> Self {
+        Self {
+            chat: Chat {
+                id: 0,
+                name: None,
+                r#type: String::new(),
+                messages: vec![],
+            },
+            last_from: None,
+            last_date: None,
+            undated: 0,
+        }
+    }
+
+    pub fn parse_page(&mut self, html: &str) {
+        let document = Html::parse_document(html);
+
+        if self.chat.name.is_none() {
+            self.chat.name = document
+                .select(&HEADER)
+                .next()
+                .map(|name| Self::collapse_text(name));
+        }
+
+        for element in document.select(&MESSAGES) {
+            if let Some(message) = self.parse_message(element) {
+                self.chat.messages.push(message);
+            }
+        }
+    }
+
+    pub fn finish(mut self) -> Chat {
+        const PERSONAL_CHAT: &str = "personal_chat";
+        const PRIVATE_GROUP: &str = "private_group";
+
+        // Даты так и не встретились: без даты сообщения только исказят период
+        self.chat.messages.drain(..self.undated);
+
+        let mut senders: Vec<&String> = self
+            .chat
+            .messages
+            .iter()
+            .filter_map(|message| message.from_id.as_ref())
+            .collect();
+        senders.sort();
+        senders.dedup();
+
+        self.chat.r#type = if senders.len() > 2 {
+            PRIVATE_GROUP
+        } else {
+            PERSONAL_CHAT
+        }
+        .to_string();
+        self.chat
+    }
+
+    fn parse_message(&mut self, element: ElementRef) -> Option<Message> {
+        let id = element
+            .value()
+            .id()
+            .and_then(|id| id.strip_prefix("message"))
+            .and_then(|id| id.parse::<i64>().ok())
+            .filter(|id| *id > 0)?;
+
+        let body = Self::child_with_class(element, "body")?;
+
+        if element.value().has_class("service", scraper::CaseSensitivity::CaseSensitive) {
+            let (date, local_date) = self.current_date();
+            return Some(Message {
+                id,
+                r#type: String::from("service"),
+                date,
+                local_date,
+                text: MessageText::Plain(Self::collapse_text(body)),
+                ..Default::default()
+            });
+        }
+
+        if let Some(date) = Self::child_with_class(body, "date")
+            .and_then(|date| date.value().attr("title"))
+            .and_then(Self::parse_date)
+        {
+            for message in &mut self.chat.messages[..self.undated] {
+                (message.date, message.local_date) = date.clone();
+            }
+            self.undated = 0;
+            self.last_date = Some(date);
+        }
+
+        if let Some(from) = Self::child_with_class(body, "from_name") {
+            self.last_from = Some(Self::collapse_text(from));
+        }
+
+        let (date, local_date) = self.current_date();
+        let mut message = Message {
+            id,
+            r#type: String::from("message"),
+            date,
+            local_date,
+            from: self.last_from.clone(),
+            from_id: self.last_from.clone(),
+            ..Default::default()
+        };
+
+        message.reply_to_message_id = Self::child_with_class(body, "reply_to")
+            .and_then(|reply| reply.select(&LINK).next())
+            .and_then(|link| link.value().attr("href"))
+            .and_then(|href| href.strip_prefix("#go_to_message"))
+            .and_then(|id| id.parse().ok());
+
+        let content = match Self::child_with_class(body, "forwarded") {
+            Some(forwarded) => {
+                message.forwarded_from = Self::child_with_class(forwarded, "from_name")
+                    .map(|from| Self::own_text(from));
+                forwarded
+            }
+            None => body,
+        };
+
+        if let Some(text) = Self::child_with_class(content, "text") {
+            let mut entities = vec![];
+            Self::collect_entities(text, "plain", &mut entities);
+            message.text_entities = entities;
+            message.text = Self::message_text(&message.text_entities);
+        }
+
+        if let Some(media) = Self::child_with_class(content, "media_wrap") {
+            Self::parse_media(media, &mut message);
+        }
+
+        Some(message)
+    }
+
+    /// Дата последнего сообщения с датой. Пока её нет, сообщение считается
+    /// недатированным и получит дату первого сообщения с датой.
+    fn current_date(&mut self) -> (DateTime<Utc>, String) {
+        match &self.last_date {
+            Some(date) => date.clone(),
+            None => {
+                self.undated += 1;
+                Default::default()
+            }
+        }
+    }
+
+    fn parse_media(media: ElementRef, message: &mut Message) {
+        let Some(item) = media.children().filter_map(ElementRef::wrap).next() else {
+            return;
+        };
+        let href = item.value().attr("href").map(str::to_string);
+        let has = |class: &str| {
+            item.value()
+                .has_class(class, scraper::CaseSensitivity::CaseSensitive)
+        };
+        let title = item
+            .select(&TITLE)
+            .next()
+            .map(|title| Self::collapse_text(title));
+        let status = item
+            .select(&STATUS)
+            .next()
+            .map(|status| Self::collapse_text(status));
+
+        if has("media_call") {
+            message.r#type = String::from("service");
+            message.action = Some(String::from("phone_call"));
+            message.actor = message.from.take();
+            message.actor_id = message.from_id.take();
+            let status = status.unwrap_or_default();
+            message.duration_seconds = Self::parse_duration(&status);
+            message.discard_reason = Some(
+                match status.to_lowercase() {
+                    status if status.contains("missed") => "missed",
+                    status if status.contains("declined") || status.contains("busy") => "busy",
+                    _ => "hangup",
+                }
+                .to_string(),
+            );
+        } else if has("photo_wrap") || has("media_photo") {
+            message.photo = href.or(status);
+        } else if has("sticker_wrap") || has("media_sticker") {
+            message.media_type = Some(String::from("sticker"));
+            message.file = href;
+            message.sticker_emoji = status.as_deref().and_then(Self::parse_sticker_emoji);
+        } else if has("animated_wrap") || has("media_animation") {
+            message.media_type = Some(String::from("animation"));
+            message.file = href;
+        } else if has("video_file_wrap")
+            || (has("media_video") && title.as_deref() != Some("Video message"))
+        {
+            message.media_type = Some(String::from("video_file"));
+            message.file = href;
+        } else if has("media_video") {
+            message.media_type = Some(String::from("video_message"));
+            message.file = href;
+            message.duration_seconds = status.as_deref().and_then(Self::parse_clock);
+        } else if has("media_voice_message") {
+            message.media_type = Some(String::from("voice_message"));
+            message.file = href;
+            message.duration_seconds = status.as_deref().and_then(Self::parse_clock);
+        } else if has("media_audio_file") {
+            message.media_type = Some(String::from("audio_file"));
+            message.file = href;
+            message.performer = title;
+            message.duration_seconds = status.as_deref().and_then(Self::parse_clock);
+        } else if has("media_file") {
+            message.file = href;
+            message.file_name = title;
+        } else if has("media_poll") {
+            message.poll = Some(Self::parse_poll(item));
+        }
+    }
+
+    /// Эмодзи стикера из статуса вида `😀, 20.5 KB`.
+    fn parse_sticker_emoji(status: &str) -> Option<String> {
+        let emoji = status.split(',').next()?.trim();
+        (!emoji.is_empty() && !emoji.chars().any(|c| c.is_alphanumeric()))
+            .then(|| emoji.to_string())
+    }
+
+    fn parse_poll(item: ElementRef) -> Poll {
+        let question = item
+            .select(&QUESTION)
+            .next()
+            .map(|question| Self::collapse_text(question))
+            .unwrap_or_default();
+        let answers = item
+            .select(&ANSWER)
+            .map(|answer| PollAnswer {
+                text: Self::collapse_text(answer)
+                    .trim_start_matches('-')
+                    .trim()
+                    .to_string(),
+                voters: 0,
+                chosen: false,
+            })
+            .collect();
+
+        Poll {
+            question,
+            closed: false,
+            total_voters: 0,
+            answers,
+        }
+    }
+
+    fn collect_entities(element: ElementRef, r#type: &str, entities: &mut Vec<MessageEntity>) {
+        for child in element.children() {
+            match child.value() {
+                Node::Text(text) => Self::push_entity(entities, r#type, text),
+                Node::Element(inner) => {
+                    let inner_ref = ElementRef::wrap(child).unwrap();
+                    let inner_type = match inner.name() {
+                        "br" => {
+                            Self::push_entity(entities, r#type, "\n");
+                            continue;
+                        }
+                        "a" => {
+                            let text = Self::collapse_text(inner_ref);
+                            if text.starts_with('@') {
+                                "mention"
+                            } else if text.starts_with('#') {
+                                "hashtag"
+                            } else if inner.attr("href") == Some(text.as_str()) {
+                                "link"
+                            } else {
+                                "text_link"
+                            }
+                        }
+                        "strong" | "b" => "bold",
+                        "em" | "i" => "italic",
+                        "u" => "underline",
+                        "s" => "strikethrough",
+                        "code" => "code",
+                        "pre" => "pre",
+                        "blockquote" => "blockquote",
+                        "span" if inner.attr("class") == Some("spoiler") => "spoiler",
+                        _ => r#type,
+                    };
+                    Self::collect_entities(inner_ref, inner_type, entities);
+                    if inner_type == "text_link" {
+                        if let Some(last) = entities.last_mut() {
+                            last.href = inner.attr("href").map(str::to_string);
+                        }
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+
+    fn push_entity(entities: &mut Vec<MessageEntity>, r#type: &str, text: &str) {
+        match entities.last_mut() {
+            Some(last) if last.r#type == r#type => last.text.push_str(text),
+            _ => entities.push(MessageEntity {
+                r#type: r#type.to_string(),
+                text: text.to_string(),
+                href: None,
+                user_id: None,
+            }),
+        }
+    }
+
+    /// Повторяет форму поля `text` JSON-экспорта: строка, если форматирования
+    /// нет, иначе список из строк и сущностей.
+    fn message_text(entities: &[MessageEntity]) -> MessageText {
+        const PLAIN: &str = "plain";
+
+        let mut entities: Vec<MessageEntity> = entities.to_vec();
+        if let Some(first) = entities.first_mut() {
+            first.text = first.text.trim_start().to_string();
+        }
+        if let Some(last) = entities.last_mut() {
+            last.text = last.text.trim_end().to_string();
+        }
+
+        if entities.iter().all(|entity| entity.r#type == PLAIN) {
+            return MessageText::Plain(entities.into_iter().map(|entity| entity.text).collect());
+        }
+
+        MessageText::Entities(
+            entities
+                .into_iter()
+                .map(|entity| match entity.r#type.as_str() {
+                    PLAIN => TextEntity::Text(entity.text),
+                    _ => TextEntity::Entity(entity),
+                })
+                .collect(),
+        )
+    }
+
+    /// Разбирает подсказку вида `13.12.2024 10:11:12 UTC+03:00` в момент времени
+    /// и локальное время в формате JSON-экспорта.
+    fn parse_date(title: &str) -> Option<(DateTime<Utc>, String)> {
+        let (local, offset) = match title.split_once(" UTC") {
+            Some((local, offset)) => (local, Some(offset)),
+            None => (title, None),
+        };
+        let naive = NaiveDateTime::parse_from_str(local.trim(), "%d.%m.%Y %H:%M:%S").ok()?;
+        let offset = match offset {
+            Some(offset) => Self::parse_offset(offset.trim())?,
+            None => FixedOffset::east_opt(0)?,
+        };
+        let date = naive.and_local_timezone(offset).single()?.with_timezone(&Utc);
+
+        Some((date, naive.format(LOCAL_DATE_FORMAT).to_string()))
+    }
+
+    fn parse_offset(offset: &str) -> Option<FixedOffset> {
+        let sign = match offset.chars().next()? {
+            '+' => 1,
+            '-' => -1,
+            _ => return None,
+        };
+        let (hours, minutes) = offset[1..].split_once(':').unwrap_or((&offset[1..], "0"));
+        let seconds = hours.parse::<i32>().ok()? * 3600 + minutes.parse::<i32>().ok()? * 60;
+
+        FixedOffset::east_opt(sign * seconds)
+    }
+
+    fn parse_duration(status: &str) -> Option<i32> {
+        let captures = DURATION_RE
+            .captures_iter(status)
+            .find(|captures| !captures[0].trim().is_empty())?;
+        let part = |index: usize| {
+            captures
+                .get(index)
+                .and_then(|value| value.as_str().parse::<i32>().ok())
+                .unwrap_or(0)
+        };
+
+        Some(part(1) * 3600 + part(2) * 60 + part(3))
+    }
+
+    fn parse_clock(status: &str) -> Option<i32> {
+        let captures = CLOCK_RE.captures(status.trim())?;
+        let part = |index: usize| {
+            captures
+                .get(index)
+                .and_then(|value| value.as_str().parse::<i32>().ok())
+                .unwrap_or(0)
+        };
+
+        Some(part(1) * 3600 + part(2) * 60 + part(3))
+    }
+
+    fn child_with_class<'a>(element: ElementRef<'a>, class: &str) -> Option<ElementRef<'a>> {
+        element.children().filter_map(ElementRef::wrap).find(|child| {
+            child
+                .value()
+                .has_class(class, scraper::CaseSensitivity::CaseSensitive)
+        })
+    }
+
+    /// Текст элемента без вложенных тегов (например, имя без даты пересылки).
+    fn own_text(element: ElementRef) -> String {
+        element
+            .children()
+            .filter_map(|child| child.value().as_text().map(|text| text.to_string()))
+            .collect::<String>()
+            .trim()
+            .to_string()
+    }
+
+    fn collapse_text(element: ElementRef) -> String {
+        element
+            .text()
+            .flat_map(str::split_whitespace)
+            .collect::<Vec<_>>()
+            .join(" ")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    const PAGE: &str = r##"<html><body><div class="page_wrap">
+<div class="page_header"><div class="content"><div class="text bold"> Bob </div></div></div>
+<div class="page_body chat_page"><div class="history">
+<div class="message service" id="message-1"><div class="body details">1 January 2025</div></div>
+<div class="message service" id="message9"><div class="body details">Me created group «X»</div></div>
+<div class="message default clearfix" id="message10"><div class="body">
+<div class="pull_right date details" title="01.01.2025 10:11:12 UTC+03:00">10:11</div>
+<div class="from_name">Me </div>
+<div class="text">Hi <a href="https://ex.com/">https://ex.com/</a> <strong>bold</strong></div></div></div>
+<div class="message default clearfix joined" id="message11"><div class="body">
+<div class="pull_right date details" title="01.01.2025 10:12:00 UTC+03:00">10:12</div>
+<div class="reply_to details">In reply to <a href="#go_to_message10">this message</a></div>
+<div class="media_wrap clearfix"><a class="sticker_wrap clearfix pull_left" href="stickers/s1.webp"></a></div></div></div>
+<div class="message default clearfix" id="message12"><div class="body">
+<div class="pull_right date details" title="01.01.2025 10:20:00 UTC+03:00">10:20</div>
+<div class="from_name">Bob </div>
+<div class="media_wrap clearfix"><div class="media clearfix pull_left media_call success"><div class="body">
+<div class="title bold">Incoming call</div><div class="status details">(3 min 12 sec)</div></div></div></div></div></div>
+</div></div></div></body></html>"##;
+
+    #[test]
+    fn parse_page_builds_chat_model() {
+        let mut parser = HtmlParser::new();
+        parser.parse_page(PAGE);
+        let chat = parser.finish();
+
+        assert_eq!(chat.id, 0);
+        assert_eq!(chat.name.as_deref(), Some("Bob"));
+        assert_eq!(chat.r#type, "personal_chat");
+        // Служебное сообщение с отрицательным id (разделитель дат) пропускается.
+        assert_eq!(chat.messages.len(), 4);
+
+        // Сообщение до первой даты получает дату следующего сообщения
+        let created = &chat.messages[0];
+        assert_eq!(created.id, 9);
+        assert_eq!(created.r#type, "service");
+        assert_eq!(created.date, chat.messages[1].date);
+        assert_eq!(created.local_date, chat.messages[1].local_date);
+
+        let text = &chat.messages[1];
+        assert_eq!(text.id, 10);
+        assert_eq!(text.from_id.as_deref(), Some("Me"));
+        assert_eq!(
+            text.date,
+            Utc.with_ymd_and_hms(2025, 1, 1, 7, 11, 12).unwrap()
+        );
+        let types: Vec<_> = text
+            .text_entities
+            .iter()
+            .map(|entity| entity.r#type.as_str())
+            .collect();
+        assert_eq!(types, ["plain", "link", "plain", "bold"]);
+
+        let sticker = &chat.messages[2];
+        assert_eq!(sticker.from_id.as_deref(), Some("Me"));
+        assert_eq!(sticker.reply_to_message_id, Some(10));
+        assert_eq!(sticker.media_type.as_deref(), Some("sticker"));
+        assert_eq!(sticker.file.as_deref(), Some("stickers/s1.webp"));
+
+        let call = &chat.messages[3];
+        assert_eq!(call.action.as_deref(), Some("phone_call"));
+        assert_eq!(call.actor_id.as_deref(), Some("Bob"));
+        assert_eq!(call.from_id, None);
+        assert_eq!(call.duration_seconds, Some(192));
+        assert_eq!(call.discard_reason.as_deref(), Some("hangup"));
+    }
+
+    #[test]
+    fn parse_page_drops_messages_without_any_date() {
+        let mut parser = HtmlParser::new();
+        parser.parse_page(
+            r#"<div class="history"><div class="message service" id="message3">
+<div class="body details">Me created group «X»</div></div></div>"#,
+        );
+
+        assert!(parser.finish().messages.is_empty());
+    }
+}
@@ -0,0 +1,222 @@
+use crate::domain::types::chat::{Chat, Message};
+use crate::domain::types::export::PersonalInformation;
+use serde::de::{DeserializeSeed, Error as SerdeError, IgnoredAny, MapAccess, SeqAccess, Visitor};
+use serde::Deserializer;
+use std::fmt;
+
+/// Заголовок чата, известный к моменту начала потока сообщений.
+#[derive(Clone, Debug)]
+pub struct ChatHeader {
+    pub id: i64,
+    pub name: Option<String>,
+    pub r#type: String,
+}
+
+impl From<&Chat> for ChatHeader {
+    fn from(chat: &Chat) -> Self {
+        Self {
+            id: chat.id,
+            name: chat.name.clone(),
+            r#type: chat.r#type.clone(),
+        }
+    }
+}
+
+/// Получатель сообщений при потоковом разборе экспорта. Сообщения передаются
+/// по одному и не накапливаются, поэтому память не зависит от размера файла.
+pub trait ChatSink {
+    /// Вызывается, если файл является экспортом всего аккаунта.
+    fn start_account(&mut self) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn personal_information(&mut self, _info: PersonalInformation) -> anyhow::Result<()> {
+        Ok(())
+    }
+
+    fn start_chat(&mut self, header: ChatHeader) -> anyhow::Result<()>;
+
+    fn message(&mut self, message: Message) -> anyhow::Result<()>;
+
+    fn end_chat(&mut self) -> anyhow::Result<()>;
+}
+
+/// Корень `result.json`: экспорт одного чата или всего аккаунта.
+pub struct ExportSeed<'s, S> {
+    pub sink: &'s mut S,
+}
+
+impl<'de, S: ChatSink> DeserializeSeed<'de> for ExportSeed<'_, S> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(ChatVisitor {
+            sink: self.sink,
+            account: true,
+        })
+    }
+}
+
+struct ChatVisitor<'s, S> {
+    sink: &'s mut S,
+    account: bool,
+}
+
+impl<'de, S: ChatSink> Visitor<'de> for ChatVisitor<'_, S> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("telegram export object")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        let mut id = None;
+        let mut name = None;
+        let mut r#type = None;
+
+        while let Some(key) = map.next_key::<String>()? {
+            match key.as_str() {
+                "id" => id = Some(map.next_value::<i64>()?),
+                "name" => name = map.next_value::<Option<String>>()?,
+                "type" => r#type = Some(map.next_value::<String>()?),
+                "personal_information" if self.account => {
+                    let info = map.next_value()?;
+                    self.sink
+                        .personal_information(info)
+                        .map_err(A::Error::custom)?;
+                }
+                "chats" | "left_chats" if self.account => {
+                    self.sink.start_account().map_err(A::Error::custom)?;
+                    map.next_value_seed(ChatListSeed {
+                        sink: &mut *self.sink,
+                    })?;
+                }
+                "messages" => {
+                    let header = ChatHeader {
+                        id: id.ok_or_else(|| A::Error::missing_field("id"))?,
+                        name: name.clone(),
+                        r#type: r#type.clone().ok_or_else(|| A::Error::missing_field("type"))?,
+                    };
+                    self.sink.start_chat(header).map_err(A::Error::custom)?;
+                    map.next_value_seed(MessagesSeed {
+                        sink: &mut *self.sink,
+                    })?;
+                    self.sink.end_chat().map_err(A::Error::custom)?;
+                }
+                _ => {
+                    map.next_value::<IgnoredAny>()?;
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+struct ChatListSeed<'s, S> {
+    sink: &'s mut S,
+}
+
+impl<'de, S: ChatSink> DeserializeSeed<'de> for ChatListSeed<'_, S> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(self)
+    }
+}
+
+impl<'de, S: ChatSink> Visitor<'de> for ChatListSeed<'_, S> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("chat list object")
+    }
+
+    fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<(), A::Error> {
+        while let Some(key) = map.next_key::<String>()? {
+            if key == "list" {
+                map.next_value_seed(ChatsSeed {
+                    sink: &mut *self.sink,
+                })?;
+            } else {
+                map.next_value::<IgnoredAny>()?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+struct ChatsSeed<'s, S> {
+    sink: &'s mut S,
+}
+
+impl<'de, S: ChatSink> DeserializeSeed<'de> for ChatsSeed<'_, S> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, S: ChatSink> Visitor<'de> for ChatsSeed<'_, S> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("list of chats")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while seq
+            .next_element_seed(ChatSeed {
+                sink: &mut *self.sink,
+            })?
+            .is_some()
+        {}
+
+        Ok(())
+    }
+}
+
+struct ChatSeed<'s, S> {
+    sink: &'s mut S,
+}
+
+impl<'de, S: ChatSink> DeserializeSeed<'de> for ChatSeed<'_, S> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_map(ChatVisitor {
+            sink: self.sink,
+            account: false,
+        })
+    }
+}
+
+struct MessagesSeed<'s, S> {
+    sink: &'s mut S,
+}
+
+impl<'de, S: ChatSink> DeserializeSeed<'de> for MessagesSeed<'_, S> {
+    type Value = ();
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<(), D::Error> {
+        deserializer.deserialize_seq(self)
+    }
+}
+
+impl<'de, S: ChatSink> Visitor<'de> for MessagesSeed<'_, S> {
+    type Value = ();
+
+    fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+        formatter.write_str("list of messages")
+    }
+
+    fn visit_seq<A: SeqAccess<'de>>(self, mut seq: A) -> Result<(), A::Error> {
+        while let Some(message) = seq.next_element::<Message>()? {
+            self.sink.message(message).map_err(A::Error::custom)?;
+        }
+
+        Ok(())
+    }
+}
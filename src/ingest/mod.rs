@@ -1,2 +1,5 @@
 pub mod data_getter;
 pub mod data_preparer;
+pub mod export_archive;
+pub mod html_parser;
+pub mod message_stream;
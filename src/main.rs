@@ -1,46 +1,54 @@
-use crate::application::services::data_processor::DataProcessor;
+use crate::application::commands;
+use crate::cli::{Cli, Command};
 use crate::config::Config;
-use crate::domain::types::chat::Chat;
-use crate::domain::types::stats::AllStats;
-use crate::infrastructure::persistence::json::Json;
-use crate::ingest::data_getter::DataGetter;
-use chrono::{TimeZone, Utc};
+use clap::Parser;
+use std::process::ExitCode;
 
 mod config;
 mod application;
+mod cli;
 mod domain;
 mod infrastructure;
 mod ingest;
 mod utils;
 
 #[tokio::main]
-async fn main() {
-    dotenv::dotenv().unwrap();
-    let config = Config::from_env().unwrap();
-
-    let data_repository = Json::new(config.app_config.output_path);
-
-    let data_processor = DataProcessor::new(data_repository);
-
-    let mut data: Chat = DataGetter::process_file_from_reader(&config.app_config.input_path)
-        .await
-        .unwrap();
-
-    data.retain_by_date(
-        Utc.with_ymd_and_hms(config.app_config.year, 1, 1, 0, 0, 0)
-            .unwrap(),
-        Utc.with_ymd_and_hms(config.app_config.year, 12, 31, 23, 59, 0)
-            .unwrap(),
-    )
-    .await;
-
-    let source_dir = std::path::Path::new(&config.app_config.input_path)
-        .parent()
-        .and_then(|path| path.to_str())
-        .unwrap_or(&config.app_config.input_path);
+async fn main() -> ExitCode {
+    let cli = Cli::parse();
+
+    match run(cli).await {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(err) => {
+            eprintln!("error: {err:#}");
+            ExitCode::FAILURE
+        }
+    }
+}
 
-    data_processor
-        .gen_stats_and_save::<AllStats>((&data, config.app_config.year, source_dir))
-        .await
-        .unwrap();
+async fn run(cli: Cli) -> anyhow::Result<()> {
+    dotenv::dotenv().ok();
+    let Config {
+        mut database_config,
+        mut app_config,
+    } = Config::from_env()?;
+
+    match cli.command.unwrap_or(Command::Stats(Box::default())) {
+        Command::Stats(args) => {
+            let format = args.apply(&mut app_config);
+            commands::stats::run(&app_config, format).await
+        }
+        Command::Import(args) => {
+            args.apply(&mut app_config, &mut database_config);
+            commands::import::run(&app_config, &database_config).await
+        }
+        Command::Inspect(args) => {
+            args.input.apply(&mut app_config);
+            commands::inspect::run(&app_config).await
+        }
+        Command::Export(args) => {
+            let format = args.apply(&mut app_config);
+            commands::export::run(&app_config, format).await
+        }
+        Command::Serve(args) => commands::serve::run(&app_config, args).await,
+    }
 }
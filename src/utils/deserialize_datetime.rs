@@ -1,19 +1,26 @@
-use chrono::{DateTime, NaiveDateTime, Utc};
+use chrono::{DateTime, Utc};
 use serde::de::Error as SerdeError;
-use serde::{Deserialize, Deserializer};
+use serde::{Deserialize, Deserializer, Serializer};
 
+/// Формат поля `date` в экспорте Telegram (локальное время без часового пояса).
+pub const LOCAL_DATE_FORMAT: &str = "%Y-%m-%dT%H:%M:%S";
+
+/// Момент времени из `date_unixtime`: секунды Unix-времени строкой.
 pub fn deserialize_datetime<'de, D>(deserializer: D) -> Result<DateTime<Utc>, D::Error>
 where
     D: Deserializer<'de>,
 {
     let s: String = Deserialize::deserialize(deserializer)?;
-    /*let offset = FixedOffset::east_opt(3 * 3600)
-    .ok_or_else(|| SerdeError::custom("Invalid timezone offset"))?;*/
-    // Парсинг даты без временной зоны
-    match NaiveDateTime::parse_from_str(&s, "%Y-%m-%dT%H:%M:%S") {
-        Ok(naive) => Ok(DateTime::from_naive_utc_and_offset(naive, Utc)),
-        Err(err) => Err(SerdeError::custom(format!(
-            "Failed to parse DateTime: {err}"
-        ))),
-    }
+    s.parse::<i64>()
+        .ok()
+        .and_then(|seconds| DateTime::from_timestamp(seconds, 0))
+        .ok_or_else(|| SerdeError::custom(format!("Failed to parse unixtime: {s}")))
+}
+
+/// Обратное к `deserialize_datetime`.
+pub fn serialize_datetime<S>(date: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error>
+where
+    S: Serializer,
+{
+    serializer.collect_str(&date.timestamp())
 }
@@ -10,8 +10,8 @@ impl From<(i64, Message)> for ActiveModel {
             chat_id: Set(chat_id),
             r#type: Set(message.r#type),
             action: Set(message.action),
-            date: Set(message.date.to_string()),
-            date_unixtime: Set(message.date_unixtime),
+            date: Set(message.local_date),
+            date_unixtime: Set(message.date.timestamp().to_string()),
             from_user: Set(message.from),
             from_id: Set(message.from_id),
             edited: Set(message.edited),
@@ -93,6 +93,9 @@ impl From<(i64, Message)> for ActiveModel {
             giveaway_information: Set(message
                 .giveaway_information
                 .map(|g| serde_json::to_value(g).ok().unwrap_or_default())),
+            reactions: Set(message
+                .reactions
+                .map(|r| serde_json::to_value(r).ok().unwrap_or_default())),
         }
     }
 }
@@ -1,6 +1,17 @@
 use std::fs::File;
+use std::io::{BufWriter, Write};
 
-pub async fn save_to_json(filename: &str, data: &impl serde::Serialize) {
-    let file = File::create(filename).expect("Failed to create file");
-    serde_json::to_writer_pretty(file, &data).expect("Failed to write JSON data");
+pub async fn save_to_json(
+    filename: &str,
+    data: &impl serde::Serialize,
+    pretty: bool,
+) -> std::io::Result<()> {
+    let mut writer = BufWriter::new(File::create(filename)?);
+    if pretty {
+        serde_json::to_writer_pretty(&mut writer, &data)?;
+    } else {
+        serde_json::to_writer(&mut writer, &data)?;
+    }
+    // Ошибка последней записи при drop была бы потеряна
+    writer.flush()
 }
@@ -46,13 +46,14 @@ type LongestConversationStats = {
 type CallsStats = {
   total_calls_durations_sec: number;
   total_calls_durations_min: number;
-  longest_call_durations_min: MessageSnapshot | null;
+  longest_call: MessageSnapshot | null;
+  longest_call_duration_min: number;
 };
 
 type OccurrenceStats = MessagesStats;
 
 type StatsData = {
-  year: number;
+  year: number | null;
   source_dir: string;
   chat_stats: {
     messages_stats: MessagesStats;
@@ -262,10 +263,8 @@ export default async function Home() {
       conversation.last_message?.date ?? null
     ),
     callMinutes: formatNumber(calls.total_calls_durations_min),
-    longestCallMinutes: formatNumber(
-      Math.round((calls.longest_call_durations_min?.duration_seconds ?? 0) / 60)
-    ),
-    longestCallTime: formatTime(calls.longest_call_durations_min?.date ?? null),
+    longestCallMinutes: formatNumber(calls.longest_call_duration_min),
+    longestCallTime: formatTime(calls.longest_call?.date ?? null),
     loveTotalCount: formatNumber(occurrences.total_messages_count),
     loveYouCount: formatNumber(occurrences.owner_messages_count),
     loveMeCount: formatNumber(occurrences.member_messages_count),